@@ -11,4 +11,4 @@ CREATE TABLE IF NOT EXISTS documents (
   is_manager_accessible   BOOLEAN         NOT NULL,
   is_public_accessible    BOOLEAN         NOT NULL,
   UNIQUE(asociation, name)
-)
+);
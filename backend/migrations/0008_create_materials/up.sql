@@ -7,4 +7,4 @@ CREATE TABLE IF NOT EXISTS materials (
   available               SMALLINT        NOT NULL,
   is_lendable             BOOLEAN         NOT NULL,
   UNIQUE(asociation, name)
-)
+);
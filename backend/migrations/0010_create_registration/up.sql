@@ -4,5 +4,4 @@ CREATE TABLE IF NOT EXISTS registration (
   user_id                 UUID            REFERENCES users,
   registration_data       JSONB           NOT NULL DEFAULT '{}'::jsonb,
   UNIQUE (activity, user_id)
-)
-
+);
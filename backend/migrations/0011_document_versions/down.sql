@@ -0,0 +1,12 @@
+DROP INDEX IF EXISTS documents_current_version;
+
+DELETE FROM documents WHERE NOT is_current;
+
+ALTER TABLE documents
+  DROP CONSTRAINT IF EXISTS documents_asociation_name_version_key;
+
+ALTER TABLE documents
+  DROP COLUMN IF EXISTS version;
+
+ALTER TABLE documents
+  ADD UNIQUE (asociation, name);
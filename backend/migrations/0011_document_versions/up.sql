@@ -0,0 +1,11 @@
+ALTER TABLE documents
+  DROP CONSTRAINT IF EXISTS documents_asociation_name_key;
+
+ALTER TABLE documents
+  ADD COLUMN version      INT             NOT NULL DEFAULT 1;
+
+ALTER TABLE documents
+  ADD UNIQUE (asociation, name, version);
+
+CREATE UNIQUE INDEX IF NOT EXISTS documents_current_version
+  ON documents (asociation, name) WHERE is_current;
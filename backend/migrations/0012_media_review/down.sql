@@ -0,0 +1,4 @@
+ALTER TABLE media
+  DROP COLUMN review_status,
+  DROP COLUMN review_comment;
+DROP TYPE IF EXISTS MEDIA_REVIEW_STATUS;
@@ -0,0 +1,5 @@
+CREATE TYPE MEDIA_REVIEW_STATUS AS ENUM ('pending', 'accepted', 'rejected');
+
+ALTER TABLE media
+  ADD COLUMN review_status  MEDIA_REVIEW_STATUS NOT NULL DEFAULT 'pending',
+  ADD COLUMN review_comment TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS invitations (
+  id                      BIGINT          GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  user_id                 UUID            NOT NULL REFERENCES users,
+  token_hash              VARCHAR(64)     UNIQUE NOT NULL,
+  expires_at              TIMESTAMP       NOT NULL,
+  used_at                 TIMESTAMP
+);
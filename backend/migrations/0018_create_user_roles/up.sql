@@ -0,0 +1,8 @@
+CREATE TYPE USER_ROLE AS ENUM ('platform_admin', 'faculty_staff', 'manager');
+
+CREATE TABLE IF NOT EXISTS user_roles (
+  id                      BIGINT          GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  user_id                 UUID            NOT NULL REFERENCES users,
+  role                    USER_ROLE       NOT NULL,
+  UNIQUE (user_id, role)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE materials
+  DROP CONSTRAINT materials_available_in_stock;
+
+ALTER TABLE lendings
+  DROP CONSTRAINT lendings_quantity_positive,
+  DROP COLUMN returned_at,
+  DROP COLUMN reviewed_at,
+  DROP COLUMN reviewed_by,
+  DROP COLUMN requested_at,
+  DROP COLUMN status;
+
+DROP TYPE IF EXISTS LENDING_STATUS;
@@ -0,0 +1,12 @@
+CREATE TYPE LENDING_STATUS AS ENUM ('requested', 'approved', 'rejected', 'returned');
+
+ALTER TABLE lendings
+  ADD COLUMN status         LENDING_STATUS  NOT NULL DEFAULT 'requested',
+  ADD COLUMN requested_at   TIMESTAMP       NOT NULL DEFAULT now(),
+  ADD COLUMN reviewed_by    UUID            REFERENCES users,
+  ADD COLUMN reviewed_at    TIMESTAMP,
+  ADD COLUMN returned_at    TIMESTAMP,
+  ADD CONSTRAINT lendings_quantity_positive CHECK (quantity > 0);
+
+ALTER TABLE materials
+  ADD CONSTRAINT materials_available_in_stock CHECK (available BETWEEN 0 AND quantity);
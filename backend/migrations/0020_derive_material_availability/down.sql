@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS materials_derive_available ON materials;
+DROP FUNCTION IF EXISTS materials_derive_available();
+DROP TRIGGER IF EXISTS lendings_track_available ON lendings;
+DROP FUNCTION IF EXISTS lendings_track_available();
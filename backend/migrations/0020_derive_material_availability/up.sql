@@ -0,0 +1,46 @@
+-- materials.available is quantity minus the units out on approved lendings.
+-- It is maintained incrementally so that concurrent approvals serialize on the
+-- material row, and the materials_available_in_stock check rejects any change
+-- that would lend more units than there are.
+UPDATE materials SET available = quantity - COALESCE((
+  SELECT SUM(lendings.quantity)
+  FROM lendings
+  WHERE lendings.material = materials.id AND lendings.status = 'approved'
+), 0);
+
+CREATE OR REPLACE FUNCTION lendings_track_available() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP <> 'INSERT' THEN
+    IF OLD.status = 'approved' THEN
+      UPDATE materials SET available = available + OLD.quantity WHERE id = OLD.material;
+    END IF;
+  END IF;
+  IF TG_OP <> 'DELETE' THEN
+    IF NEW.status = 'approved' THEN
+      UPDATE materials SET available = available - NEW.quantity WHERE id = NEW.material;
+    END IF;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER lendings_track_available
+  AFTER INSERT OR UPDATE OR DELETE ON lendings
+  FOR EACH ROW EXECUTE FUNCTION lendings_track_available();
+
+-- Only lendings_track_available may set available; direct writes are
+-- overridden and quantity changes shift it by the same amount.
+CREATE OR REPLACE FUNCTION materials_derive_available() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    NEW.available := NEW.quantity;
+  ELSIF pg_trigger_depth() = 1 THEN
+    NEW.available := OLD.available + NEW.quantity - OLD.quantity;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER materials_derive_available
+  BEFORE INSERT OR UPDATE ON materials
+  FOR EACH ROW EXECUTE FUNCTION materials_derive_available();
@@ -0,0 +1,2 @@
+ALTER TABLE lendings
+  DROP COLUMN last_reminder_date;
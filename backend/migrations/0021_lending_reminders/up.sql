@@ -0,0 +1,2 @@
+ALTER TABLE lendings
+  ADD COLUMN last_reminder_date DATE;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS reservations (
+  id                      BIGINT          GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  material                BIGINT          NOT NULL REFERENCES materials,
+  user_id                 UUID            NOT NULL REFERENCES users,
+  activity                BIGINT          REFERENCES activities ON DELETE CASCADE,
+  quantity                SMALLINT        NOT NULL CHECK (quantity > 0),
+  starts_at               TIMESTAMP       NOT NULL,
+  ends_at                 TIMESTAMP       NOT NULL,
+  CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS reservations_material_window
+  ON reservations (material, starts_at, ends_at);
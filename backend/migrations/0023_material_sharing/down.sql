@@ -0,0 +1,5 @@
+ALTER TABLE lendings
+  DROP COLUMN borrower_asociation;
+
+ALTER TABLE materials
+  DROP COLUMN is_shared;
@@ -0,0 +1,5 @@
+ALTER TABLE materials
+  ADD COLUMN is_shared    BOOLEAN         NOT NULL DEFAULT false;
+
+ALTER TABLE lendings
+  ADD COLUMN borrower_asociation UUID     REFERENCES asociations;
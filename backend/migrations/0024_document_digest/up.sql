@@ -0,0 +1,4 @@
+-- SHA-256 of the stored file, hex encoded. Documents uploaded before it was
+-- recorded have none.
+ALTER TABLE documents
+  ADD COLUMN digest       VARCHAR(64);
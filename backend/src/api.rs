@@ -1,14 +1,31 @@
 use crate::auth;
+use crate::error::{ApiError, Result};
+use crate::mail;
 use crate::models;
+use crate::policy::{
+    Admin, Authorized, BoardOfAsociation, ChairOfAsociation, MemberOfAsociation, Or, Policy,
+    Requirement,
+};
 use crate::schema;
 use crate::settings::ServerData;
+use crate::storage;
+use crate::uploads;
 use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use diesel::{delete, update, BelongingToDsl, SelectableHelper};
 use jwt::VerifyWithKey;
-use poem::{error, web::Data, Request, Result};
+use poem::http::{header, StatusCode};
+use poem::{web::Data, Request};
 use poem_openapi::{
-    auth::Bearer, param::Path, param::Query, payload::Json, OpenApi, SecurityScheme, Tags,
+    auth::Bearer,
+    param::Header,
+    param::Path,
+    param::Query,
+    payload::{Attachment, AttachmentType, Binary, Json, Response},
+    OpenApi, SecurityScheme, Tags,
 };
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::vec;
 use uuid::Uuid;
 
@@ -21,7 +38,628 @@ pub struct JWTBearerAuth(auth::AuthScheme);
 async fn auth_checker(req: &Request, bearer: Bearer) -> Option<auth::AuthScheme> {
     let server_data = req.data::<ServerData>().unwrap();
     let server_key = &server_data.settings.private_key;
-    VerifyWithKey::<auth::AuthScheme>::verify_with_key(bearer.token.as_str(), server_key).ok()
+    let auth_scheme =
+        VerifyWithKey::<auth::AuthScheme>::verify_with_key(bearer.token.as_str(), server_key)
+            .ok()?;
+    server_data
+        .run(move |conn, _| {
+            auth::check_session(conn, &auth_scheme)?;
+            Ok(auth_scheme)
+        })
+        .await
+        .ok()
+}
+
+/// The `materials_available_in_stock` check only fails when a change would
+/// lend out more units than a material has, which is a conflict with the
+/// current lendings rather than a malformed request.
+fn stock_conflict(error: DieselError) -> ApiError {
+    match error {
+        DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, ref info)
+            if info.constraint_name() == Some("materials_available_in_stock") =>
+        {
+            ApiError::conflict("Not enough units of the material are in stock")
+        }
+        error => ApiError::from(error),
+    }
+}
+
+/// Who a lending penalty looks at: a member borrowing for themselves, or an
+/// asociation borrowing shared materials.
+enum Borrower {
+    User(Uuid),
+    Asociation(Uuid),
+}
+
+/// Fails when `borrower` holds lendings of `asociation` that are more than
+/// `days` overdue, for the penalties of [`models::api::LendingPolicy`].
+fn check_overdue_penalty(
+    conn: &mut PgConnection,
+    asociation: Uuid,
+    borrower: Borrower,
+    days: Option<i64>,
+    message: &str,
+) -> Result<()> {
+    use schema::lendings;
+    use schema::materials;
+
+    let Some(days) = days else {
+        return Ok(());
+    };
+    let limit = time::OffsetDateTime::now_utc().date() - time::Duration::days(days);
+    let query = lendings::table
+        .inner_join(materials::table)
+        .filter(materials::asociation.eq(asociation))
+        .filter(lendings::status.eq(models::database::LendingStatus::Approved))
+        .filter(lendings::due_date.lt(limit))
+        .into_boxed();
+    let query = match borrower {
+        Borrower::User(user) => query.filter(lendings::user_id.eq(user)),
+        Borrower::Asociation(borrower) => query.filter(lendings::borrower_asociation.eq(borrower)),
+    };
+    let overdue = query.count().get_result::<i64>(conn)?;
+    if overdue > 0 {
+        return Err(ApiError::conflict(message));
+    }
+    Ok(())
+}
+
+/// Whether moving the expiry date of a membership from `current` to `new`
+/// renews it. Memberships without an expiry date, like pending requests, are
+/// renewed by any date.
+fn renews_membership(current: Option<time::Date>, new: Option<time::Date>) -> bool {
+    new > current
+}
+
+/// Fails when the membership of `user` in `asociation` is being renewed from
+/// `current_expiry` to `new_expiry` while the member holds items overdue past
+/// the renewal penalty of the asociation.
+fn check_renewal_penalty(
+    conn: &mut PgConnection,
+    asociation: Uuid,
+    user: Uuid,
+    current_expiry: Option<time::Date>,
+    new_expiry: Option<time::Date>,
+) -> Result<()> {
+    if !renews_membership(current_expiry, new_expiry) {
+        return Ok(());
+    }
+    let policy = models::api::LendingPolicy::of(
+        &schema::asociations::table
+            .find(asociation)
+            .select(models::database::Asociation::as_select())
+            .first(conn)?,
+    );
+    check_overdue_penalty(
+        conn,
+        asociation,
+        Borrower::User(user),
+        policy.block_renewal_after_days,
+        "The member has overdue items and cannot be renewed",
+    )
+}
+
+/// Fails when booking `quantity` units of `material` from `starts_at` to
+/// `ends_at` would take more units than it has at some moment, counting the
+/// overlapping reservations and the lendings still out. The material row is
+/// locked so that concurrent bookings are checked one after the other, which
+/// needs a transaction around the check and the insert.
+fn check_material_capacity(
+    conn: &mut PgConnection,
+    material: &models::database::Material,
+    quantity: i16,
+    starts_at: time::PrimitiveDateTime,
+    ends_at: time::PrimitiveDateTime,
+) -> Result<()> {
+    use schema::lendings;
+    use schema::materials;
+    use schema::reservations;
+
+    // The stock may have changed since `material` was read.
+    let in_stock = materials::table
+        .find(material.id)
+        .select(materials::quantity)
+        .for_update()
+        .first::<i16>(conn)?;
+
+    let reserved = reservations::table
+        .filter(reservations::material.eq(material.id))
+        .filter(reservations::starts_at.lt(ends_at))
+        .filter(reservations::ends_at.gt(starts_at))
+        .select((
+            reservations::quantity,
+            reservations::starts_at,
+            reservations::ends_at,
+        ))
+        .load::<(i16, time::PrimitiveDateTime, time::PrimitiveDateTime)>(conn)?;
+    let lent = lendings::table
+        .filter(lendings::material.eq(material.id))
+        .filter(lendings::status.eq(models::database::LendingStatus::Approved))
+        .filter(lendings::due_date.ge(starts_at.date()))
+        .select((lendings::quantity, lendings::due_date))
+        .load::<(i16, time::Date)>(conn)?;
+
+    let peak = peak_usage(starts_at, ends_at, &reserved, &lent);
+    if peak + i32::from(quantity) > i32::from(in_stock) {
+        return Err(ApiError::conflict(format!(
+            "Only {} units of {} are free in that window",
+            (i32::from(in_stock) - peak).max(0),
+            material.name
+        )));
+    }
+    Ok(())
+}
+
+/// Most units of a material in use at once from `starts_at` to `ends_at`,
+/// given the quantity and window of its reservations and the quantity and due
+/// date of its approved lendings. Windows are half-open, so back-to-back
+/// bookings do not add up, and a lending is out until the end of its due date.
+fn peak_usage(
+    starts_at: time::PrimitiveDateTime,
+    ends_at: time::PrimitiveDateTime,
+    reserved: &[(i16, time::PrimitiveDateTime, time::PrimitiveDateTime)],
+    lent: &[(i16, time::Date)],
+) -> i32 {
+    let mut changes = vec![];
+    for &(units, start, end) in reserved {
+        if start < ends_at && end > starts_at {
+            changes.push((start.max(starts_at), i32::from(units)));
+            changes.push((end, -i32::from(units)));
+        }
+    }
+    for &(units, due_date) in lent {
+        let returned = (due_date + time::Duration::days(1)).midnight();
+        if returned > starts_at {
+            changes.push((starts_at, i32::from(units)));
+            changes.push((returned, -i32::from(units)));
+        }
+    }
+    // Returns sort before takes at the same moment.
+    changes.sort();
+
+    let mut taken = 0;
+    let mut peak = 0;
+    for (_, units) in changes {
+        taken += units;
+        peak = peak.max(taken);
+    }
+    peak
+}
+
+/// Reservations in start order: the upcoming ones of the materials of an
+/// asociation, or every one booked for an activity.
+fn material_reservations(
+    conn: &mut PgConnection,
+    asociation: Option<Uuid>,
+    activity: Option<i64>,
+) -> Result<Vec<models::api::FullReservation>> {
+    use schema::materials;
+    use schema::reservations;
+    use schema::users;
+
+    let mut query = reservations::table
+        .inner_join(materials::table)
+        .inner_join(users::table)
+        .order(reservations::starts_at)
+        .into_boxed();
+    if let Some(asociation) = asociation {
+        query = query
+            .filter(materials::asociation.eq(asociation))
+            .filter(reservations::ends_at.gt(auth::now_timestamp()));
+    }
+    if let Some(activity) = activity {
+        query = query.filter(reservations::activity.eq(activity));
+    }
+
+    Ok(query
+        .select((
+            models::database::Reservation::as_select(),
+            models::database::Material::as_select(),
+            models::api::UserProfile::as_select(),
+        ))
+        .load(conn)?
+        .into_iter()
+        .map(
+            |(reservation, material, user)| models::api::FullReservation {
+                reservation,
+                material,
+                user,
+            },
+        )
+        .collect())
+}
+
+/// Builds the mail to the board of `to` about a loan between asociations,
+/// copying the manager responsible for the lending asociation `owner` through
+/// their `material_email`.
+fn shared_lending_mail(
+    conn: &mut PgConnection,
+    owner: Uuid,
+    to: Uuid,
+    subject: String,
+    body: String,
+) -> Result<mail::Mail> {
+    use schema::asociations;
+    use schema::managers;
+
+    let to = asociations::table
+        .find(to)
+        .select(asociations::email)
+        .first::<String>(conn)?;
+    let material_email = asociations::table
+        .inner_join(managers::table)
+        .filter(asociations::id.eq(owner))
+        .select(managers::material_email)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten();
+
+    Ok(mail::Mail {
+        to,
+        cc: material_email.into_iter().collect(),
+        subject,
+        body,
+    })
+}
+
+/// Looks a lending up, making sure it is of a material of `asociation`.
+fn asociation_lending(
+    conn: &mut PgConnection,
+    asociation: Uuid,
+    lending_id: i64,
+) -> Result<models::database::Lending> {
+    use schema::lendings;
+    use schema::materials;
+
+    Ok(lendings::table
+        .inner_join(materials::table)
+        .filter(materials::asociation.eq(asociation))
+        .filter(lendings::id.eq(lending_id))
+        .select(models::database::Lending::as_select())
+        .first(conn)?)
+}
+
+/// Lendings of the materials of `asociation`, oldest first. `overdue_only`
+/// keeps those past their due date.
+fn asociation_lendings(
+    conn: &mut PgConnection,
+    asociation: Uuid,
+    status: Option<models::database::LendingStatus>,
+    overdue_only: bool,
+) -> Result<Vec<models::api::FullLending>> {
+    use schema::lendings;
+    use schema::materials;
+    use schema::users;
+
+    let mut query = lendings::table
+        .inner_join(materials::table)
+        .inner_join(users::table)
+        .filter(materials::asociation.eq(asociation))
+        .order(lendings::requested_at)
+        .into_boxed();
+    if let Some(status) = status {
+        query = query.filter(lendings::status.eq(status));
+    }
+    if overdue_only {
+        let today = time::OffsetDateTime::now_utc().date();
+        query = query.filter(lendings::due_date.lt(today));
+    }
+
+    Ok(query
+        .select((
+            models::database::Lending::as_select(),
+            models::database::Material::as_select(),
+            models::api::UserProfile::as_select(),
+        ))
+        .load(conn)?
+        .into_iter()
+        .map(|(lending, material, user)| models::api::FullLending {
+            lending,
+            material,
+            user,
+        })
+        .collect())
+}
+
+/// Stores an uploaded media file under `media_uuid` along with its resized
+/// variants, returning the stored keys with the original first. Files are
+/// removed again if any of them cannot be stored. Decoding and writing block,
+/// so this runs off the async runtime.
+fn store_media(
+    storage: &dyn storage::FileStorage,
+    media_uuid: &Uuid,
+    extension: &'static str,
+    contents: &[u8],
+) -> Result<Vec<String>> {
+    let variants = uploads::render_variants(contents).map_err(ApiError::bad_request)?;
+
+    let mut stored_keys = vec![];
+    let files = std::iter::once((uploads::Variant::Original, extension, contents)).chain(
+        variants
+            .iter()
+            .map(|(variant, extension, contents)| (*variant, *extension, contents.as_slice())),
+    );
+    for (variant, extension, contents) in files {
+        let key = uploads::media_key(media_uuid, variant, extension);
+        stored_keys.push(key.clone());
+        if let Err(e) = storage.store(&key, contents) {
+            remove_media(storage, &stored_keys);
+            return Err(ApiError::internal(e));
+        }
+    }
+    Ok(stored_keys)
+}
+
+fn remove_media(storage: &dyn storage::FileStorage, keys: &[String]) {
+    for key in keys {
+        storage.remove(key).ok();
+    }
+}
+
+/// Runs every statement of `f` in a single transaction on `conn`.
+pub fn transaction<T, F>(conn: &mut PgConnection, f: F) -> Result<T>
+where
+    F: FnOnce(&mut PgConnection) -> QueryResult<T>,
+{
+    conn.transaction(f).map_err(ApiError::from)
+}
+
+fn document_policy(document: &models::database::Document) -> Policy {
+    if document.is_public_accessible {
+        return Policy::Public;
+    }
+    let policy = Policy::BoardOf(document.asociation);
+    if document.is_manager_accessible {
+        return policy.or(Policy::ManagerOf(document.asociation));
+    }
+    policy
+}
+
+/// Boards of any organizing asociation may edit an activity, as may the
+/// managers responsible for it.
+fn activity_editors(activity_organizers: &[Uuid]) -> Policy {
+    Policy::Admin
+        .or(Policy::ManagerOfAny(activity_organizers.to_vec()))
+        .or(Policy::OrganizerOf(activity_organizers.to_vec()))
+}
+
+/// Removing an activity, or an organizer from it, needs the consent of every
+/// organizing board.
+fn activity_owners(activity_organizers: &[Uuid]) -> Policy {
+    Policy::Admin
+        .or(Policy::ManagerOfAny(activity_organizers.to_vec()))
+        .or(Policy::OwnerOf(activity_organizers.to_vec()))
+}
+
+/// Co-organizers may not drop each other from an activity.
+fn organizer_removers(activity_organizers: &[Uuid], organizer: Uuid) -> Policy {
+    Policy::Admin
+        .or(Policy::ManagerOfAny(activity_organizers.to_vec()))
+        .or(Policy::BoardOf(organizer))
+}
+
+/// Editors of the activity in the `activity_id` path parameter.
+pub struct ActivityEditors;
+
+#[poem::async_trait]
+impl Requirement for ActivityEditors {
+    async fn policy(req: &Request, data: &ServerData) -> Result<Policy> {
+        Ok(activity_editors(&path_organizers(req, data).await?))
+    }
+}
+
+/// Owners of the activity in the `activity_id` path parameter.
+pub struct ActivityOwners;
+
+#[poem::async_trait]
+impl Requirement for ActivityOwners {
+    async fn policy(req: &Request, data: &ServerData) -> Result<Policy> {
+        Ok(activity_owners(&path_organizers(req, data).await?))
+    }
+}
+
+/// Managers responsible for the activity in the `activity_id` path parameter.
+pub struct ActivityManagers;
+
+#[poem::async_trait]
+impl Requirement for ActivityManagers {
+    async fn policy(req: &Request, data: &ServerData) -> Result<Policy> {
+        Ok(Policy::ManagerOfAny(path_organizers(req, data).await?))
+    }
+}
+
+async fn path_organizers(req: &Request, data: &ServerData) -> Result<Vec<Uuid>> {
+    let activity_id = req
+        .raw_path_param("activity_id")
+        .unwrap_or_default()
+        .parse::<i64>()
+        .map_err(|e| ApiError::invalid_field("activity_id", e))?;
+    data.run(move |conn, _| {
+        // Missing activities are reported as such rather than as forbidden,
+        // since nobody organizes them.
+        schema::activities::table
+            .find(activity_id)
+            .select(schema::activities::id)
+            .first::<i64>(conn)?;
+        organizers_of(conn, activity_id)
+    })
+    .await
+}
+
+fn organizers_of(conn: &mut PgConnection, activity_id: i64) -> Result<Vec<Uuid>> {
+    use schema::organizers::dsl::*;
+
+    Ok(organizers
+        .filter(activity.eq(activity_id))
+        .select(asociation)
+        .load::<Uuid>(conn)?)
+}
+
+fn refresh_media_acceptance(conn: &mut PgConnection, activity_id: i64) -> QueryResult<()> {
+    use schema::activities;
+    use schema::media;
+
+    let reviews = media::table
+        .filter(media::activity.eq(activity_id))
+        .select((media::kind, media::review_status))
+        .load::<(
+            models::database::MediaKind,
+            models::database::MediaReviewStatus,
+        )>(conn)?;
+
+    let is_accepted = uploads::media_accepted(&reviews);
+
+    update(activities::table.filter(activities::id.eq(activity_id)))
+        .set(activities::is_media_accepted.eq(is_accepted))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn insert_document_version(
+    conn: &mut PgConnection,
+    mut new_version: models::database::NaiveDocument,
+) -> QueryResult<models::database::Document> {
+    use schema::documents::dsl::*;
+
+    conn.transaction(|conn| {
+        let latest = documents
+            .filter(asociation.eq(new_version.asociation))
+            .filter(name.eq(&new_version.name))
+            .select(diesel::dsl::max(version))
+            .first::<Option<i32>>(conn)?;
+
+        update(
+            documents
+                .filter(asociation.eq(new_version.asociation))
+                .filter(name.eq(&new_version.name)),
+        )
+        .set(is_current.eq(false))
+        .execute(conn)?;
+
+        new_version.version = latest.unwrap_or(0) + 1;
+        new_version.is_current = true;
+        diesel::insert_into(documents)
+            .values(new_version)
+            .returning(models::database::Document::as_returning())
+            .get_result(conn)
+    })
+}
+
+fn file_digest(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn parse_byte_range(range: &str, length: usize) -> Result<Option<(usize, usize)>, ()> {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    // Multiple ranges are allowed to be answered with the full representation.
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+
+    let (start, end) = match (start.parse::<usize>(), end.parse::<usize>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(length.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, length.saturating_sub(1)),
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (length.saturating_sub(suffix), length.saturating_sub(1))
+        }
+        _ => return Ok(None),
+    };
+    if start >= length {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+fn document_response(
+    data: &ServerData,
+    document: &models::database::Document,
+    range: Option<&str>,
+    if_none_match: Option<&str>,
+) -> Result<Response<Attachment<Vec<u8>>>> {
+    // Stored files never change, so older documents without a digest can be
+    // told apart by their row.
+    let etag = match &document.digest {
+        Some(digest) => format!("\"{digest}\""),
+        None => format!("\"{}-{}\"", document.id, document.version),
+    };
+
+    if if_none_match.is_some_and(|tags| {
+        tags.split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == etag || t == "*")
+    }) {
+        return Ok(Response::new(Attachment::new(Vec::new()))
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag));
+    }
+
+    let contents = data
+        .storage
+        .retrieve(&document.path)
+        .map_err(ApiError::internal)?;
+    let content_type = storage::content_type(&document.path);
+    let extension = std::path::Path::new(&document.path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| format!(".{e}"))
+        .unwrap_or_default();
+    let file_name: String = format!("{}{}", document.name, extension)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let attachment_type = if content_type == "application/pdf"
+        || content_type.starts_with("text/")
+        || content_type.starts_with("image/")
+    {
+        AttachmentType::Inline
+    } else {
+        AttachmentType::Attachment
+    };
+
+    let length = contents.len();
+    let (status, body, content_range) = match range.map(|r| parse_byte_range(r, length)) {
+        Some(Err(())) => {
+            return Ok(Response::new(Attachment::new(Vec::new()))
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{length}")));
+        }
+        Some(Ok(Some((start, end)))) => (
+            StatusCode::PARTIAL_CONTENT,
+            contents[start..=end].to_vec(),
+            Some(format!("bytes {start}-{end}/{length}")),
+        ),
+        _ => (StatusCode::OK, contents, None),
+    };
+
+    let mut response = Response::new(
+        Attachment::new(body)
+            .attachment_type(attachment_type)
+            .filename(file_name),
+    )
+    .status(status)
+    .header(header::CONTENT_TYPE, content_type)
+    .header(header::ACCEPT_RANGES, "bytes")
+    .header(header::ETAG, etag);
+    if let Some(content_range) = content_range {
+        response = response.header(header::CONTENT_RANGE, content_range);
+    }
+
+    Ok(response)
 }
 
 #[derive(Tags)]
@@ -30,8 +668,10 @@ enum ApiTags {
     Members,
     Activities,
     Materials,
+    Lendings,
     Documents,
     Session,
+    Users,
 }
 
 #[OpenApi]
@@ -43,13 +683,14 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Asociation>>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = asociations
-            .select(models::database::Asociation::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = asociations
+                .select(models::database::Asociation::as_select())
+                .load(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(path = "/asociations", method = "post", tag = "ApiTags::Asociations")]
@@ -57,19 +698,19 @@ impl DanubitApi {
         &self,
         post_data: Json<models::database::NaiveAsociation>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<Admin>,
     ) -> Result<Json<models::database::Asociation>> {
         use schema::asociations::dsl::*;
-        auth::check_if_admin(&auth.0, &data.0.settings)?;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = diesel::insert_into(asociations)
-            .values(post_data.0)
-            .returning(models::database::Asociation::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = diesel::insert_into(asociations)
+                .values(post_data.0)
+                .returning(models::database::Asociation::as_returning())
+                .get_result(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -84,15 +725,17 @@ impl DanubitApi {
     ) -> Result<Json<models::database::Asociation>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-        let result = asociations
-            .filter(id.eq(uuid))
-            .select(models::database::Asociation::as_select())
-            .first(conn)
-            .map_err(error::NotFound)?;
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let result = asociations
+                .filter(id.eq(uuid))
+                .select(models::database::Asociation::as_select())
+                .first(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -107,14 +750,15 @@ impl DanubitApi {
     ) -> Result<Json<models::database::Asociation>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = asociations
-            .filter(short_name.eq(asociation_short_name.0))
-            .select(models::database::Asociation::as_select())
-            .first(conn)
-            .map_err(error::NotFound)?;
+        data.run(move |conn, _| {
+            let result = asociations
+                .filter(short_name.eq(asociation_short_name.0))
+                .select(models::database::Asociation::as_select())
+                .first(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -127,22 +771,22 @@ impl DanubitApi {
         asociation_id: Path<String>,
         update_data: Json<models::database::Asociation>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<models::database::Asociation>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        let result = update(asociations.filter(id.eq(uuid)))
-            .set(update_data.0)
-            .returning(models::database::Asociation::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
+            let result = update(asociations.filter(id.eq(uuid)))
+                .set(update_data.0)
+                .returning(models::database::Asociation::as_returning())
+                .get_result(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(path = "/managers", method = "get", tag = "ApiTags::Asociations")]
@@ -152,13 +796,14 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Manager>>> {
         use schema::managers::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = managers
-            .select(models::database::Manager::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = managers
+                .select(models::database::Manager::as_select())
+                .load(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -170,42 +815,42 @@ impl DanubitApi {
         &self,
         asociation_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<Vec<models::api::FullMember>>> {
         use schema::members::dsl::*;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
-
-        let requests = members
-            .filter(asociation.eq(uuid))
-            .filter(is_accepted.eq(false))
-            .inner_join(users::table)
-            .select((
-                models::database::Member::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let result = requests
-            .into_iter()
-            .map(|(m, u)| models::api::FullMember {
-                id: m.id,
-                user: u,
-                asociation: m.asociation,
-                is_accepted: m.is_accepted,
-                accepted_date: m.accepted_date,
-                expiry_date: m.expiry_date,
-                label: m.label,
-                board_status: m.board_status,
-            })
-            .collect();
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let requests = members
+                .filter(asociation.eq(uuid))
+                .filter(is_accepted.eq(false))
+                .inner_join(users::table)
+                .select((
+                    models::database::Member::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?;
+
+            let result = requests
+                .into_iter()
+                .map(|(m, u)| models::api::FullMember {
+                    id: m.id,
+                    user: u,
+                    asociation: m.asociation,
+                    is_accepted: m.is_accepted,
+                    accepted_date: m.accepted_date,
+                    expiry_date: m.expiry_date,
+                    label: m.label,
+                    board_status: m.board_status,
+                })
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -222,31 +867,30 @@ impl DanubitApi {
     ) -> Result<Json<models::database::Member>> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-
-        if auth.0.sub != post_data.0.user_id {
-            return Err(error::Error::from_string(
-                "Cannot request membership for another user.",
-                poem::http::StatusCode::BAD_REQUEST,
-            ));
-        }
-
-        let member = models::database::NaiveMember {
-            user_id: post_data.0.user_id,
-            asociation: asociation_id.0,
-            is_accepted: false,
-            accepted_date: None,
-            expiry_date: None,
-            label: None,
-            board_status: models::database::BoardStatus::False,
-        };
-        let result = diesel::insert_into(members)
-            .values(member)
-            .returning(models::database::Member::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            if auth.0.sub != post_data.0.user_id {
+                return Err(ApiError::validation(
+                    "Cannot request membership for another user.",
+                ));
+            }
+
+            let member = models::database::NaiveMember {
+                user_id: post_data.0.user_id,
+                asociation: asociation_id.0,
+                is_accepted: false,
+                accepted_date: None,
+                expiry_date: None,
+                label: None,
+                board_status: models::database::BoardStatus::False,
+            };
+            let result = diesel::insert_into(members)
+                .values(member)
+                .returning(models::database::Member::as_returning())
+                .get_result(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -259,38 +903,49 @@ impl DanubitApi {
         asociation_id: Path<String>,
         member_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<Or<Admin, BoardOfAsociation>>,
     ) -> Result<Json<models::database::Member>> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        auth::check_if_admin(&auth.0, &data.0.settings).or(auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &asociation_uuid,
-        ))?;
-
-        let today = time::OffsetDateTime::now_utc().date();
-        let result = update(
-            members
+            let current_expiry = members
                 .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .set((
-            is_accepted.eq(true),
-            accepted_date.eq(today),
-            expiry_date.eq(today
+                .filter(user_id.eq(user_uuid))
+                .select(expiry_date)
+                .first::<Option<time::Date>>(conn)?;
+            let today = time::OffsetDateTime::now_utc().date();
+            let new_expiry = today
                 .replace_year(today.year() + 1)
-                .map_err(error::InternalServerError)?),
-        ))
-        .returning(models::database::Member::as_returning())
-        .get_result(conn)
-        .map_err(error::InternalServerError)?;
+                .map_err(ApiError::internal)?;
+            check_renewal_penalty(
+                conn,
+                asociation_uuid,
+                user_uuid,
+                current_expiry,
+                Some(new_expiry),
+            )?;
+
+            let result = update(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .set((
+                is_accepted.eq(true),
+                accepted_date.eq(today),
+                expiry_date.eq(new_expiry),
+            ))
+            .returning(models::database::Member::as_returning())
+            .get_result(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -303,29 +958,26 @@ impl DanubitApi {
         asociation_id: Path<String>,
         member_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<()> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &asociation_uuid,
-        )?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        delete(
-            members
-                .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .execute(conn)
-        .map_err(error::InternalServerError)?;
+            delete(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .execute(conn)?;
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     #[oai(
@@ -337,42 +989,42 @@ impl DanubitApi {
         &self,
         asociation_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<Vec<models::api::FullMember>>> {
         use schema::members::dsl::*;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
-
-        let requests = members
-            .filter(asociation.eq(uuid))
-            .filter(is_accepted.eq(true))
-            .inner_join(users::table)
-            .select((
-                models::database::Member::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let result = requests
-            .into_iter()
-            .map(|(m, u)| models::api::FullMember {
-                id: m.id,
-                user: u,
-                asociation: m.asociation,
-                is_accepted: m.is_accepted,
-                accepted_date: m.accepted_date,
-                expiry_date: m.expiry_date,
-                label: m.label,
-                board_status: m.board_status,
-            })
-            .collect();
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let requests = members
+                .filter(asociation.eq(uuid))
+                .filter(is_accepted.eq(true))
+                .inner_join(users::table)
+                .select((
+                    models::database::Member::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?;
+
+            let result = requests
+                .into_iter()
+                .map(|(m, u)| models::api::FullMember {
+                    id: m.id,
+                    user: u,
+                    asociation: m.asociation,
+                    is_accepted: m.is_accepted,
+                    accepted_date: m.accepted_date,
+                    expiry_date: m.expiry_date,
+                    label: m.label,
+                    board_status: m.board_status,
+                })
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -386,31 +1038,41 @@ impl DanubitApi {
         member_id: Path<String>,
         update_data: Json<models::database::Member>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<models::database::Member>> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &asociation_uuid,
-        )?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        let result = update(
-            members
+            let current_expiry = members
                 .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .set(update_data.0)
-        .returning(models::database::Member::as_returning())
-        .get_result(conn)
-        .map_err(error::InternalServerError)?;
+                .filter(user_id.eq(user_uuid))
+                .select(expiry_date)
+                .first::<Option<time::Date>>(conn)?;
+            check_renewal_penalty(
+                conn,
+                asociation_uuid,
+                user_uuid,
+                current_expiry,
+                update_data.0.expiry_date,
+            )?;
+
+            let result = update(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .set(update_data.0)
+            .returning(models::database::Member::as_returning())
+            .get_result(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -423,29 +1085,26 @@ impl DanubitApi {
         asociation_id: Path<String>,
         member_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<()> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &asociation_uuid,
-        )?;
-
-        delete(
-            members
-                .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .execute(conn)
-        .map_err(error::InternalServerError)?;
+            delete(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .execute(conn)?;
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     #[oai(
@@ -460,22 +1119,24 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Member>>> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        let result = members
-            .filter(asociation.eq(uuid))
-            .filter(is_accepted.eq(true))
-            .filter(board_status.eq_any(vec![
-                models::database::BoardStatus::Board,
-                models::database::BoardStatus::ViceChair,
-                models::database::BoardStatus::Chair,
-            ]))
-            .select(models::database::Member::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let result = members
+                .filter(asociation.eq(uuid))
+                .filter(is_accepted.eq(true))
+                .filter(board_status.eq_any(vec![
+                    models::database::BoardStatus::Board,
+                    models::database::BoardStatus::ViceChair,
+                    models::database::BoardStatus::Chair,
+                ]))
+                .select(models::database::Member::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -489,31 +1150,28 @@ impl DanubitApi {
         member_id: Path<String>,
         update_data: Json<models::database::Member>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<Or<Admin, ChairOfAsociation>>,
     ) -> Result<Json<models::database::Member>> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        auth::check_if_admin(&auth.0, &data.0.settings).or(auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Chair,
-            &asociation_uuid,
-        ))?;
-
-        let result = update(
-            members
-                .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .set(update_data.0)
-        .returning(models::database::Member::as_returning())
-        .get_result(conn)
-        .map_err(error::InternalServerError)?;
+            let result = update(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .set(update_data.0)
+            .returning(models::database::Member::as_returning())
+            .get_result(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -526,31 +1184,28 @@ impl DanubitApi {
         asociation_id: Path<String>,
         member_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<Or<Admin, ChairOfAsociation>>,
     ) -> Result<()> {
         use schema::members::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user_uuid = Uuid::try_parse(&member_id.0).map_err(error::BadRequest)?;
-        let asociation_uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let user_uuid = Uuid::try_parse(&member_id.0)
+                .map_err(|e| ApiError::invalid_field("member_id", e))?;
+            let asociation_uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        auth::check_if_admin(&auth.0, &data.0.settings).or(auth::check_permissions(
-            &auth.0,
-            models::database::BoardStatus::Chair,
-            &asociation_uuid,
-        ))?;
-
-        update(
-            members
-                .filter(asociation.eq(asociation_uuid))
-                .filter(user_id.eq(user_uuid)),
-        )
-        .set(board_status.eq(models::database::BoardStatus::False))
-        .returning(models::database::Member::as_returning())
-        .get_result(conn)
-        .map_err(error::InternalServerError)?;
+            update(
+                members
+                    .filter(asociation.eq(asociation_uuid))
+                    .filter(user_id.eq(user_uuid)),
+            )
+            .set(board_status.eq(models::database::BoardStatus::False))
+            .returning(models::database::Member::as_returning())
+            .get_result(conn)?;
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     #[oai(
@@ -565,16 +1220,19 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Document>>> {
         use schema::documents::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-        let result = documents
-            .filter(asociation.eq(uuid))
-            .filter(is_public_accessible.eq(true))
-            .select(models::database::Document::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let result = documents
+                .filter(asociation.eq(uuid))
+                .filter(is_public_accessible.eq(true))
+                .filter(is_current.eq(true))
+                .select(models::database::Document::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -586,22 +1244,23 @@ impl DanubitApi {
         &self,
         asociation_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<Vec<models::database::Document>>> {
         use schema::documents::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
 
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
+            let result = documents
+                .filter(asociation.eq(uuid))
+                .filter(is_current.eq(true))
+                .select(models::database::Document::as_select())
+                .load(conn)?;
 
-        let result = documents
-            .filter(asociation.eq(uuid))
-            .select(models::database::Document::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -614,9 +1273,64 @@ impl DanubitApi {
         asociation_id: Path<String>,
         upload: models::api::DocumentUpload,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<models::database::Document>> {
-        todo!()
+        use schema::documents::dsl::*;
+
+        let uuid = Uuid::try_parse(&asociation_id.0)
+            .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+        let document_data = upload.file_data.0;
+        if document_data.asociation != uuid {
+            return Err(ApiError::validation(
+                "Document must belong to the asociation it is uploaded to.",
+            ));
+        }
+
+        let key = storage::document_key(&uuid, upload.upload.file_name());
+        let contents = uploads::read_limited(upload.upload, data.settings.max_document_size)
+            .await
+            .map_err(ApiError::bad_request)?
+            .ok_or(ApiError::payload_too_large("Document is too large."))?;
+
+        data.run(move |conn, data| {
+            let existing = documents
+                .filter(asociation.eq(uuid))
+                .filter(name.eq(&document_data.name))
+                .count()
+                .get_result::<i64>(conn)?;
+            if existing > 0 {
+                return Err(ApiError::conflict(
+                    "A document with this name already exists.",
+                ));
+            }
+
+            data.storage
+                .store(&key, &contents)
+                .map_err(ApiError::internal)?;
+
+            let new_document = models::database::NaiveDocument {
+                asociation: uuid,
+                activity: document_data.activity,
+                name: document_data.name,
+                description: document_data.description,
+                path: key.clone(),
+                creation_date: time::OffsetDateTime::now_utc().date(),
+                is_current: true,
+                is_important: document_data.is_important,
+                is_manager_accessible: document_data.is_manager_accessible,
+                is_public_accessible: document_data.is_public_accessible,
+                version: 1,
+                digest: Some(file_digest(&contents)),
+            };
+            let result = insert_document_version(conn, new_document).map_err(|e| {
+                data.storage.remove(&key).ok();
+                ApiError::from(e)
+            })?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -628,86 +1342,353 @@ impl DanubitApi {
         &self,
         asociation_id: Path<String>,
         document_id: Path<String>,
-        update_data: Json<models::api::DocumentDescription>,
+        upload: models::api::DocumentUpload,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
-    ) -> Result<Json<Vec<models::database::Document>>> {
-        todo!()
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<models::database::Document>> {
+        use schema::documents::dsl::*;
+
+        let uuid = Uuid::try_parse(&asociation_id.0)
+            .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+        let key = storage::document_key(&uuid, upload.upload.file_name());
+        let contents = uploads::read_limited(upload.upload, data.settings.max_document_size)
+            .await
+            .map_err(ApiError::bad_request)?
+            .ok_or(ApiError::payload_too_large("Document is too large."))?;
+
+        data.run(move |conn, data| {
+            let document_id = &document_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("document_id", e))?;
+            let document = documents
+                .filter(asociation.eq(uuid))
+                .filter(id.eq(document_id))
+                .select(models::database::Document::as_select())
+                .first(conn)?;
+
+            let document_data = upload.file_data.0;
+            if document_data.asociation != uuid || document_data.name != document.name {
+                return Err(ApiError::validation(
+                    "A new version must keep the asociation and name of the document.",
+                ));
+            }
+
+            data.storage
+                .store(&key, &contents)
+                .map_err(ApiError::internal)?;
+
+            let new_version = models::database::NaiveDocument {
+                asociation: uuid,
+                activity: document_data.activity,
+                name: document.name,
+                description: document_data.description,
+                path: key.clone(),
+                creation_date: time::OffsetDateTime::now_utc().date(),
+                is_current: true,
+                is_important: document_data.is_important,
+                is_manager_accessible: document_data.is_manager_accessible,
+                is_public_accessible: document_data.is_public_accessible,
+                version: document.version + 1,
+                digest: Some(file_digest(&contents)),
+            };
+            let result = insert_document_version(conn, new_version).map_err(|e| {
+                data.storage.remove(&key).ok();
+                ApiError::from(e)
+            })?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
-        path = "/asociations/:asociation_id/materials",
+        path = "/asociations/:asociation_id/documents/:document_id/versions",
         method = "get",
-        tag = "ApiTags::Materials"
+        tag = "ApiTags::Documents"
     )]
-    async fn list_all_materials(
+    async fn list_document_versions(
         &self,
         asociation_id: Path<String>,
+        document_id: Path<String>,
         data: Data<&ServerData>,
-    ) -> Result<Json<Vec<models::database::Material>>> {
-        use schema::materials::dsl::*;
-
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-        let result = materials
-            .filter(asociation.eq(uuid))
-            .select(models::database::Material::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        auth: JWTBearerAuth,
+    ) -> Result<Json<Vec<models::database::Document>>> {
+        use schema::documents::dsl::*;
 
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let document_id = &document_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("document_id", e))?;
+
+            let document = documents
+                .filter(asociation.eq(uuid))
+                .filter(id.eq(document_id))
+                .select(models::database::Document::as_select())
+                .first(conn)?;
+
+            document_policy(&document).authorize(&auth.0)?;
+
+            let result = documents
+                .filter(asociation.eq(uuid))
+                .filter(name.eq(&document.name))
+                .order(version.desc())
+                .select(models::database::Document::as_select())
+                .load(conn)?
+                .into_iter()
+                .filter(|d| document_policy(d).allows(Some(&auth.0)))
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
-        path = "/asociations/:asociation_id/lendableMaterials",
-        method = "get",
-        tag = "ApiTags::Materials"
+        path = "/asociations/:asociation_id/documents/:document_id/restore",
+        method = "post",
+        tag = "ApiTags::Documents"
     )]
-    async fn list_lendable_materials(
+    async fn restore_document_version(
         &self,
         asociation_id: Path<String>,
+        document_id: Path<String>,
         data: Data<&ServerData>,
-    ) -> Result<Json<Vec<models::database::Material>>> {
-        use schema::materials::dsl::*;
-
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-        let result = materials
-            .filter(asociation.eq(uuid))
-            .filter(is_lendable.eq(true))
-            .select(models::database::Material::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<models::database::Document>> {
+        use schema::documents::dsl::*;
 
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let document_id = &document_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("document_id", e))?;
+            let document = documents
+                .filter(asociation.eq(uuid))
+                .filter(id.eq(document_id))
+                .select(models::database::Document::as_select())
+                .first(conn)?;
+
+            // Restoring appends a new version pointing at the old file, so the
+            // history keeps a record of the restore itself.
+            let restored = models::database::NaiveDocument {
+                asociation: document.asociation,
+                activity: document.activity,
+                name: document.name,
+                description: document.description,
+                path: document.path,
+                creation_date: time::OffsetDateTime::now_utc().date(),
+                is_current: true,
+                is_important: document.is_important,
+                is_manager_accessible: document.is_manager_accessible,
+                is_public_accessible: document.is_public_accessible,
+                version: document.version,
+                digest: document.digest,
+            };
+            let result = insert_document_version(conn, restored).map_err(ApiError::from)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
-        path = "/asociations/:asociation_id/materials",
-        method = "post",
-        tag = "ApiTags::Materials"
+        path = "/asociations/:asociation_id/publicDocuments/:document_id/content",
+        method = "get",
+        tag = "ApiTags::Documents"
     )]
-    async fn create_material(
+    async fn get_public_document_content(
         &self,
         asociation_id: Path<String>,
-        post_data: Json<models::database::NaiveMaterial>,
+        document_id: Path<String>,
+        #[oai(name = "Range")] range: Header<Option<String>>,
+        #[oai(name = "If-None-Match")] if_none_match: Header<Option<String>>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
-    ) -> Result<Json<models::database::Material>> {
-        use schema::materials::dsl::*;
+    ) -> Result<Response<Attachment<Vec<u8>>>> {
+        use schema::documents::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, data| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let document_id = &document_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("document_id", e))?;
+
+            let document = documents
+                .filter(asociation.eq(uuid))
+                .filter(id.eq(document_id))
+                .filter(is_public_accessible.eq(true))
+                .select(models::database::Document::as_select())
+                .first(conn)?;
+
+            document_response(
+                data,
+                &document,
+                range.0.as_deref(),
+                if_none_match.0.as_deref(),
+            )
+        })
+        .await
+    }
 
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
+    #[oai(
+        path = "/asociations/:asociation_id/documents/:document_id/content",
+        method = "get",
+        tag = "ApiTags::Documents"
+    )]
+    async fn get_document_content(
+        &self,
+        asociation_id: Path<String>,
+        document_id: Path<String>,
+        #[oai(name = "Range")] range: Header<Option<String>>,
+        #[oai(name = "If-None-Match")] if_none_match: Header<Option<String>>,
+        data: Data<&ServerData>,
+        auth: JWTBearerAuth,
+    ) -> Result<Response<Attachment<Vec<u8>>>> {
+        use schema::documents::dsl::*;
 
-        let result = diesel::insert_into(materials)
-            .values(post_data.0)
-            .returning(models::database::Material::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, data| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let document_id = &document_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("document_id", e))?;
+
+            let document = documents
+                .filter(asociation.eq(uuid))
+                .filter(id.eq(document_id))
+                .select(models::database::Document::as_select())
+                .first(conn)?;
+
+            document_policy(&document).authorize(&auth.0)?;
+
+            document_response(
+                data,
+                &document,
+                range.0.as_deref(),
+                if_none_match.0.as_deref(),
+            )
+        })
+        .await
+    }
 
-        Ok(Json(result))
+    #[oai(
+        path = "/asociations/:asociation_id/materials",
+        method = "get",
+        tag = "ApiTags::Materials"
+    )]
+    async fn list_all_materials(
+        &self,
+        asociation_id: Path<String>,
+        data: Data<&ServerData>,
+    ) -> Result<Json<Vec<models::database::Material>>> {
+        use schema::materials::dsl::*;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let result = materials
+                .filter(asociation.eq(uuid))
+                .select(models::database::Material::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/lendableMaterials",
+        method = "get",
+        tag = "ApiTags::Materials"
+    )]
+    async fn list_lendable_materials(
+        &self,
+        asociation_id: Path<String>,
+        data: Data<&ServerData>,
+    ) -> Result<Json<Vec<models::database::Material>>> {
+        use schema::materials::dsl::*;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let result = materials
+                .filter(asociation.eq(uuid))
+                .filter(is_lendable.eq(true))
+                .select(models::database::Material::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(path = "/sharedMaterials", method = "get", tag = "ApiTags::Materials")]
+    async fn list_shared_materials(
+        &self,
+        asociation_filter: Query<Option<Uuid>>,
+        data: Data<&ServerData>,
+    ) -> Result<Json<Vec<models::database::Material>>> {
+        use schema::materials::dsl::*;
+
+        data.run(move |conn, _| {
+            let mut query = materials
+                .filter(is_shared.eq(true))
+                .order((asociation, name))
+                .into_boxed();
+            if let Some(asociation_id) = asociation_filter.0 {
+                query = query.filter(asociation.eq(asociation_id));
+            }
+            let result = query
+                .select(models::database::Material::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/materials",
+        method = "post",
+        tag = "ApiTags::Materials"
+    )]
+    async fn create_material(
+        &self,
+        asociation_id: Path<String>,
+        post_data: Json<models::database::NaiveMaterial>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<models::database::Material>> {
+        use schema::materials::dsl::*;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            if post_data.asociation != uuid {
+                return Err(ApiError::invalid_field(
+                    "asociation",
+                    "Does not match the asociation in the path",
+                ));
+            }
+
+            let result = diesel::insert_into(materials)
+                .values(post_data.0)
+                .returning(models::database::Material::as_returning())
+                .get_result(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -719,25 +1700,33 @@ impl DanubitApi {
         &self,
         asociation_id: Path<String>,
         material_id: Path<String>,
-        update_data: Json<models::database::Material>,
+        update_data: Json<models::api::MaterialUpdate>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<Json<models::database::Material>> {
         use schema::materials::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
-
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
-
-        let material_id = &material_id.0.parse::<i64>().map_err(error::BadRequest)?;
-        let result = update(materials.filter(id.eq(material_id)))
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let material_id = &material_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("material_id", e))?;
+            let result = update(
+                materials
+                    .filter(asociation.eq(uuid))
+                    .filter(id.eq(material_id)),
+            )
             .set(update_data.0)
             .returning(models::database::Material::as_returning())
             .get_result(conn)
-            .map_err(error::InternalServerError)?;
+            .map_err(stock_conflict)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -750,21 +1739,579 @@ impl DanubitApi {
         asociation_id: Path<String>,
         material_id: Path<String>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<BoardOfAsociation>,
     ) -> Result<()> {
         use schema::materials::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let uuid = Uuid::try_parse(&asociation_id.0).map_err(error::BadRequest)?;
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let material_id = &material_id
+                .0
+                .parse::<i64>()
+                .map_err(|e| ApiError::invalid_field("material_id", e))?;
+            let deleted = delete(
+                materials
+                    .filter(asociation.eq(uuid))
+                    .filter(id.eq(material_id)),
+            )
+            .execute(conn)?;
+            if deleted == 0 {
+                return Err(ApiError::not_found("Material not found"));
+            }
+
+            Ok(())
+        })
+        .await
+    }
 
-        auth::check_permissions(&auth.0, models::database::BoardStatus::Board, &uuid)?;
+    #[oai(
+        path = "/asociations/:asociation_id/materials/:material_id/lendings",
+        method = "post",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn request_lending(
+        &self,
+        asociation_id: Path<String>,
+        material_id: Path<i64>,
+        post_data: Json<models::api::LendingRequest>,
+        data: Data<&ServerData>,
+        auth: Authorized<MemberOfAsociation>,
+    ) -> Result<Json<models::database::Lending>> {
+        use schema::lendings;
+        use schema::materials;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let policy = models::api::LendingPolicy::of(
+                &schema::asociations::table
+                    .find(uuid)
+                    .select(models::database::Asociation::as_select())
+                    .first(conn)?,
+            );
+            check_overdue_penalty(
+                conn,
+                uuid,
+                Borrower::User(auth.0.sub),
+                policy.block_loans_after_days,
+                "Return your overdue items before borrowing again",
+            )?;
+
+            let material = materials::table
+                .filter(materials::asociation.eq(uuid))
+                .filter(materials::id.eq(material_id.0))
+                .select(models::database::Material::as_select())
+                .first(conn)?;
+            if !material.is_lendable {
+                return Err(ApiError::conflict("The material is not lendable"));
+            }
+            if post_data.0.quantity < 1 || post_data.0.quantity > material.quantity {
+                return Err(ApiError::invalid_field(
+                    "quantity",
+                    format!("Must be between 1 and {}", material.quantity),
+                ));
+            }
+            if post_data.0.due_date < time::OffsetDateTime::now_utc().date() {
+                return Err(ApiError::invalid_field(
+                    "due_date",
+                    "The due date is in the past",
+                ));
+            }
+
+            let result = diesel::insert_into(lendings::table)
+                .values(models::database::NaiveLending {
+                    material: material.id,
+                    user_id: auth.0.sub,
+                    quantity: post_data.0.quantity,
+                    due_date: post_data.0.due_date,
+                    borrower_asociation: None,
+                })
+                .returning(models::database::Lending::as_returning())
+                .get_result(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/materials/:material_id/sharedLendings",
+        method = "post",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn request_shared_lending(
+        &self,
+        asociation_id: Path<String>,
+        material_id: Path<i64>,
+        post_data: Json<models::api::SharedLendingRequest>,
+        data: Data<&ServerData>,
+        auth: JWTBearerAuth,
+    ) -> Result<Json<models::database::Lending>> {
+        use schema::lendings;
+        use schema::materials;
+
+        let (result, mail) = data
+            .run(move |conn, _| {
+                let uuid = Uuid::try_parse(&asociation_id.0)
+                    .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+                let request = post_data.0;
+
+                Policy::BoardOf(request.borrower_asociation).authorize(&auth.0)?;
+
+                if request.borrower_asociation == uuid {
+                    return Err(ApiError::invalid_field(
+                        "borrower_asociation",
+                        "Asociations cannot borrow their own materials",
+                    ));
+                }
+                let material = materials::table
+                    .filter(materials::asociation.eq(uuid))
+                    .filter(materials::id.eq(material_id.0))
+                    .select(models::database::Material::as_select())
+                    .first(conn)?;
+                if !material.is_shared {
+                    return Err(ApiError::conflict(
+                        "The material is not shared with other asociations",
+                    ));
+                }
+                let policy = models::api::LendingPolicy::of(
+                    &schema::asociations::table
+                        .find(uuid)
+                        .select(models::database::Asociation::as_select())
+                        .first(conn)?,
+                );
+                check_overdue_penalty(
+                    conn,
+                    uuid,
+                    Borrower::Asociation(request.borrower_asociation),
+                    policy.block_loans_after_days,
+                    "Return the overdue items of your asociation before borrowing again",
+                )?;
+                if request.quantity < 1 || request.quantity > material.quantity {
+                    return Err(ApiError::invalid_field(
+                        "quantity",
+                        format!("Must be between 1 and {}", material.quantity),
+                    ));
+                }
+                if request.due_date < time::OffsetDateTime::now_utc().date() {
+                    return Err(ApiError::invalid_field(
+                        "due_date",
+                        "The due date is in the past",
+                    ));
+                }
+
+                let result = diesel::insert_into(lendings::table)
+                    .values(models::database::NaiveLending {
+                        material: material.id,
+                        user_id: auth.0.sub,
+                        quantity: request.quantity,
+                        due_date: request.due_date,
+                        borrower_asociation: Some(request.borrower_asociation),
+                    })
+                    .returning(models::database::Lending::as_returning())
+                    .get_result(conn)?;
+
+                let borrower = schema::asociations::table
+                    .find(request.borrower_asociation)
+                    .select(schema::asociations::short_name)
+                    .first::<String>(conn)?;
+                let mail = shared_lending_mail(
+                    conn,
+                    uuid,
+                    uuid,
+                    format!("{borrower} asks to borrow {}", material.name),
+                    format!(
+                        "{borrower} would like to borrow {} x {} until {}.\n\
+                        Review the request in the lendings of your asociation.",
+                        request.quantity, material.name, request.due_date
+                    ),
+                )?;
+
+                Ok((result, mail))
+            })
+            .await?;
 
-        let material_id = &material_id.0.parse::<i64>().map_err(error::BadRequest)?;
-        delete(materials.filter(id.eq(material_id)))
-            .execute(conn)
-            .map_err(error::InternalServerError)?;
+        data.send_mail(mail).await;
 
-        Ok(())
+        Ok(Json(result))
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/borrowedLendings",
+        method = "get",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn list_borrowed_lendings(
+        &self,
+        asociation_id: Path<String>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<Vec<models::api::FullLending>>> {
+        use schema::lendings;
+        use schema::materials;
+        use schema::users;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let result = lendings::table
+                .inner_join(materials::table)
+                .inner_join(users::table)
+                .filter(lendings::borrower_asociation.eq(uuid))
+                .order(lendings::requested_at)
+                .select((
+                    models::database::Lending::as_select(),
+                    models::database::Material::as_select(),
+                    models::api::UserProfile::as_select(),
+                ))
+                .load(conn)?
+                .into_iter()
+                .map(|(lending, material, user)| models::api::FullLending {
+                    lending,
+                    material,
+                    user,
+                })
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/lendings",
+        method = "get",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn list_lendings(
+        &self,
+        asociation_id: Path<String>,
+        status: Query<Option<models::database::LendingStatus>>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<Vec<models::api::FullLending>>> {
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let result = asociation_lendings(conn, uuid, status.0, false)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/lendings/overdue",
+        method = "get",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn list_overdue_lendings(
+        &self,
+        asociation_id: Path<String>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<Vec<models::api::FullLending>>> {
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let result = asociation_lendings(
+                conn,
+                uuid,
+                Some(models::database::LendingStatus::Approved),
+                true,
+            )?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/lendings/:lending_id/review",
+        method = "put",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn review_lending(
+        &self,
+        asociation_id: Path<String>,
+        lending_id: Path<i64>,
+        review: Json<models::api::LendingReview>,
+        data: Data<&ServerData>,
+        auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<models::database::Lending>> {
+        use schema::lendings;
+
+        let (result, mail) = data
+            .run(move |conn, _| {
+                let uuid = Uuid::try_parse(&asociation_id.0)
+                    .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+                let lending = asociation_lending(conn, uuid, lending_id.0)?;
+                let status = if review.0.is_approved {
+                    models::database::LendingStatus::Approved
+                } else {
+                    models::database::LendingStatus::Rejected
+                };
+
+                let result =
+                    conn.transaction::<_, ApiError, _>(|conn| {
+                        if review.0.is_approved {
+                            let material = schema::materials::table
+                                .find(lending.material)
+                                .select(models::database::Material::as_select())
+                                .first(conn)?;
+                            // The units go out now and stay out until the end of the
+                            // due date, which must not eat into reservations.
+                            check_material_capacity(
+                                conn,
+                                &material,
+                                lending.quantity,
+                                auth::now_timestamp(),
+                                (lending.due_date + time::Duration::days(1)).midnight(),
+                            )?;
+                        }
+
+                        update(lendings::table.filter(lendings::id.eq(lending.id)).filter(
+                            lendings::status.eq(models::database::LendingStatus::Requested),
+                        ))
+                        .set((
+                            lendings::status.eq(status),
+                            lendings::reviewed_by.eq(auth.0.sub),
+                            lendings::reviewed_at.eq(auth::now_timestamp()),
+                        ))
+                        .returning(models::database::Lending::as_returning())
+                        .get_result(conn)
+                        .optional()
+                        .map_err(stock_conflict)?
+                        .ok_or(ApiError::conflict("The lending has already been reviewed"))
+                    })?;
+
+                let mut mail = None;
+                if let Some(borrower) = result.borrower_asociation {
+                    let verdict = if review.0.is_approved {
+                        "approved"
+                    } else {
+                        "rejected"
+                    };
+                    let material_name = schema::materials::table
+                        .find(result.material)
+                        .select(schema::materials::name)
+                        .first::<String>(conn)?;
+                    mail = Some(shared_lending_mail(
+                        conn,
+                        uuid,
+                        borrower,
+                        format!("Loan of {material_name} {verdict}"),
+                        format!(
+                            "The request to borrow {} x {material_name} until {} was {verdict}.",
+                            result.quantity, result.due_date
+                        ),
+                    )?);
+                }
+
+                Ok((result, mail))
+            })
+            .await?;
+
+        if let Some(mail) = mail {
+            data.send_mail(mail).await;
+        }
+
+        Ok(Json(result))
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/lendings/:lending_id/return",
+        method = "put",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn return_lending(
+        &self,
+        asociation_id: Path<String>,
+        lending_id: Path<i64>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<models::database::Lending>> {
+        use schema::lendings;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let lending = asociation_lending(conn, uuid, lending_id.0)?;
+            let result = update(
+                lendings::table
+                    .filter(lendings::id.eq(lending.id))
+                    .filter(lendings::status.eq(models::database::LendingStatus::Approved)),
+            )
+            .set((
+                lendings::status.eq(models::database::LendingStatus::Returned),
+                lendings::returned_at.eq(auth::now_timestamp()),
+            ))
+            .returning(models::database::Lending::as_returning())
+            .get_result(conn)
+            .optional()?
+            .ok_or(ApiError::conflict("The lending is not out"))?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/materials/:material_id/reservations",
+        method = "post",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn reserve_material(
+        &self,
+        asociation_id: Path<String>,
+        material_id: Path<i64>,
+        post_data: Json<models::api::ReservationRequest>,
+        data: Data<&ServerData>,
+        auth: JWTBearerAuth,
+    ) -> Result<Json<models::database::Reservation>> {
+        use schema::materials;
+        use schema::reservations;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+            let request = post_data.0;
+
+            let material = materials::table
+                .filter(materials::asociation.eq(uuid))
+                .filter(materials::id.eq(material_id.0))
+                .select(models::database::Material::as_select())
+                .first(conn)?;
+
+            // Activities may book any material of their organizers, members
+            // only what is lendable.
+            match request.activity {
+                Some(activity_id) => {
+                    let activity_organizers = organizers_of(conn, activity_id)?;
+                    activity_editors(&activity_organizers).authorize(&auth.0)?;
+                    if !activity_organizers.contains(&uuid) {
+                        return Err(ApiError::invalid_field(
+                            "activity",
+                            "The asociation does not organize the activity",
+                        ));
+                    }
+                }
+                None => {
+                    Policy::MemberOf(uuid).authorize(&auth.0)?;
+                    if !material.is_lendable {
+                        return Err(ApiError::conflict("The material is not lendable"));
+                    }
+                }
+            }
+            if request.quantity < 1 || request.quantity > material.quantity {
+                return Err(ApiError::invalid_field(
+                    "quantity",
+                    format!("Must be between 1 and {}", material.quantity),
+                ));
+            }
+            if request.ends_at <= request.starts_at {
+                return Err(ApiError::invalid_field(
+                    "ends_at",
+                    "The reservation must end after it starts",
+                ));
+            }
+            if request.starts_at < auth::now_timestamp() {
+                return Err(ApiError::invalid_field(
+                    "starts_at",
+                    "The reservation starts in the past",
+                ));
+            }
+
+            let result = conn.transaction::<_, ApiError, _>(|conn| {
+                check_material_capacity(
+                    conn,
+                    &material,
+                    request.quantity,
+                    request.starts_at,
+                    request.ends_at,
+                )?;
+                Ok(diesel::insert_into(reservations::table)
+                    .values(models::database::NaiveReservation {
+                        material: material.id,
+                        user_id: auth.0.sub,
+                        activity: request.activity,
+                        quantity: request.quantity,
+                        starts_at: request.starts_at,
+                        ends_at: request.ends_at,
+                    })
+                    .returning(models::database::Reservation::as_returning())
+                    .get_result(conn)?)
+            })?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/reservations",
+        method = "get",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn list_reservations(
+        &self,
+        asociation_id: Path<String>,
+        data: Data<&ServerData>,
+        _auth: Authorized<BoardOfAsociation>,
+    ) -> Result<Json<Vec<models::api::FullReservation>>> {
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let result = material_reservations(conn, Some(uuid), None)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/asociations/:asociation_id/reservations/:reservation_id",
+        method = "delete",
+        tag = "ApiTags::Lendings"
+    )]
+    async fn cancel_reservation(
+        &self,
+        asociation_id: Path<String>,
+        reservation_id: Path<i64>,
+        data: Data<&ServerData>,
+        auth: JWTBearerAuth,
+    ) -> Result<()> {
+        use schema::materials;
+        use schema::reservations;
+
+        data.run(move |conn, _| {
+            let uuid = Uuid::try_parse(&asociation_id.0)
+                .map_err(|e| ApiError::invalid_field("asociation_id", e))?;
+
+            let reservation = reservations::table
+                .inner_join(materials::table)
+                .filter(materials::asociation.eq(uuid))
+                .filter(reservations::id.eq(reservation_id.0))
+                .select(models::database::Reservation::as_select())
+                .first(conn)?;
+            if reservation.user_id != auth.0.sub {
+                Policy::BoardOf(uuid).authorize(&auth.0)?;
+            }
+
+            delete(reservations::table.find(reservation.id)).execute(conn)?;
+
+            Ok(())
+        })
+        .await
     }
 
     #[oai(
@@ -782,52 +2329,53 @@ impl DanubitApi {
         use schema::organizers;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let public_activities = activities::table
-            .filter(activities::access.eq(models::database::ActivityAccess::Public))
-            .select(models::database::Activity::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let mut activity_organizers = models::database::Organizer::belonging_to(&public_activities)
-            .inner_join(asociations::table)
-            .inner_join(users::table)
-            .into_boxed();
-
-        if asociation_filter.0.is_some() {
-            activity_organizers =
-                activity_organizers.filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
-        };
-
-        let activity_organizers: Vec<(
-            models::database::Organizer,
-            models::database::Asociation,
-            models::database::User,
-        )> = activity_organizers
-            .select((
-                models::database::Organizer::as_select(),
-                models::database::Asociation::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let result: Vec<models::api::FullActivity> = activity_organizers
-            .grouped_by(&public_activities)
-            .into_iter()
-            .zip(public_activities)
-            .map(|(org, act)| {
-                let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
-                models::api::FullActivity {
-                    activity: act,
-                    organizers: asocs,
-                    people_in_charge: people,
-                }
-            })
-            .filter(|a| !a.organizers.is_empty())
-            .collect();
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let public_activities = activities::table
+                .filter(activities::access.eq(models::database::ActivityAccess::Public))
+                .select(models::database::Activity::as_select())
+                .load(conn)?;
+
+            let mut activity_organizers =
+                models::database::Organizer::belonging_to(&public_activities)
+                    .inner_join(asociations::table)
+                    .inner_join(users::table)
+                    .into_boxed();
+
+            if asociation_filter.0.is_some() {
+                activity_organizers = activity_organizers
+                    .filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
+            };
+
+            let activity_organizers: Vec<(
+                models::database::Organizer,
+                models::database::Asociation,
+                models::database::User,
+            )> = activity_organizers
+                .select((
+                    models::database::Organizer::as_select(),
+                    models::database::Asociation::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?;
+
+            let result: Vec<models::api::FullActivity> = activity_organizers
+                .grouped_by(&public_activities)
+                .into_iter()
+                .zip(public_activities)
+                .map(|(org, act)| {
+                    let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
+                    models::api::FullActivity {
+                        activity: act,
+                        organizers: asocs,
+                        people_in_charge: people,
+                    }
+                })
+                .filter(|a| !a.organizers.is_empty())
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -846,52 +2394,53 @@ impl DanubitApi {
         use schema::organizers;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let member_activities = activities::table
-            .filter(activities::access.eq(models::database::ActivityAccess::Members))
-            .select(models::database::Activity::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let mut activity_organizers = models::database::Organizer::belonging_to(&member_activities)
-            .inner_join(asociations::table)
-            .inner_join(users::table)
-            .filter(organizers::asociation.eq_any(auth.0.member_of))
-            .into_boxed();
-
-        if asociation_filter.0.is_some() {
-            activity_organizers =
-                activity_organizers.filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
-        };
-
-        let activity_organizers: Vec<(
-            models::database::Organizer,
-            models::database::Asociation,
-            models::database::User,
-        )> = activity_organizers
-            .select((
-                models::database::Organizer::as_select(),
-                models::database::Asociation::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let result: Vec<models::api::FullActivity> = activity_organizers
-            .grouped_by(&member_activities)
-            .into_iter()
-            .zip(member_activities)
-            .map(|(org, act)| {
-                let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
-                models::api::FullActivity {
-                    activity: act,
-                    organizers: asocs,
-                    people_in_charge: people,
-                }
-            })
-            .collect();
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let member_activities = activities::table
+                .filter(activities::access.eq(models::database::ActivityAccess::Members))
+                .select(models::database::Activity::as_select())
+                .load(conn)?;
+
+            let mut activity_organizers =
+                models::database::Organizer::belonging_to(&member_activities)
+                    .inner_join(asociations::table)
+                    .inner_join(users::table)
+                    .filter(organizers::asociation.eq_any(auth.0.member_of))
+                    .into_boxed();
+
+            if asociation_filter.0.is_some() {
+                activity_organizers = activity_organizers
+                    .filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
+            };
+
+            let activity_organizers: Vec<(
+                models::database::Organizer,
+                models::database::Asociation,
+                models::database::User,
+            )> = activity_organizers
+                .select((
+                    models::database::Organizer::as_select(),
+                    models::database::Asociation::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?;
+
+            let result: Vec<models::api::FullActivity> = activity_organizers
+                .grouped_by(&member_activities)
+                .into_iter()
+                .zip(member_activities)
+                .map(|(org, act)| {
+                    let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
+                    models::api::FullActivity {
+                        activity: act,
+                        organizers: asocs,
+                        people_in_charge: people,
+                    }
+                })
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(path = "/boardActivities", method = "get", tag = "ApiTags::Activities")]
@@ -906,52 +2455,53 @@ impl DanubitApi {
         use schema::organizers;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let board_activities = activities::table
-            .filter(activities::access.eq(models::database::ActivityAccess::Board))
-            .select(models::database::Activity::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let mut activity_organizers = models::database::Organizer::belonging_to(&board_activities)
-            .inner_join(asociations::table)
-            .inner_join(users::table)
-            .filter(organizers::asociation.eq_any(auth.0.board_of))
-            .into_boxed();
-
-        if asociation_filter.0.is_some() {
-            activity_organizers =
-                activity_organizers.filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
-        };
-
-        let activity_organizers: Vec<(
-            models::database::Organizer,
-            models::database::Asociation,
-            models::database::User,
-        )> = activity_organizers
-            .select((
-                models::database::Organizer::as_select(),
-                models::database::Asociation::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?;
-
-        let result: Vec<models::api::FullActivity> = activity_organizers
-            .grouped_by(&board_activities)
-            .into_iter()
-            .zip(board_activities)
-            .map(|(org, act)| {
-                let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
-                models::api::FullActivity {
-                    activity: act,
-                    organizers: asocs,
-                    people_in_charge: people,
-                }
-            })
-            .collect();
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let board_activities = activities::table
+                .filter(activities::access.eq(models::database::ActivityAccess::Board))
+                .select(models::database::Activity::as_select())
+                .load(conn)?;
+
+            let mut activity_organizers =
+                models::database::Organizer::belonging_to(&board_activities)
+                    .inner_join(asociations::table)
+                    .inner_join(users::table)
+                    .filter(organizers::asociation.eq_any(auth.0.board_of))
+                    .into_boxed();
+
+            if asociation_filter.0.is_some() {
+                activity_organizers = activity_organizers
+                    .filter(organizers::asociation.eq(asociation_filter.0.unwrap()));
+            };
+
+            let activity_organizers: Vec<(
+                models::database::Organizer,
+                models::database::Asociation,
+                models::database::User,
+            )> = activity_organizers
+                .select((
+                    models::database::Organizer::as_select(),
+                    models::database::Asociation::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?;
+
+            let result: Vec<models::api::FullActivity> = activity_organizers
+                .grouped_by(&board_activities)
+                .into_iter()
+                .zip(board_activities)
+                .map(|(org, act)| {
+                    let (asocs, people) = org.into_iter().map(|(_, a, b)| (a, b)).unzip();
+                    models::api::FullActivity {
+                        activity: act,
+                        organizers: asocs,
+                        people_in_charge: people,
+                    }
+                })
+                .collect();
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(path = "/activities", method = "post", tag = "ApiTags::Activities")]
@@ -964,38 +2514,48 @@ impl DanubitApi {
         use schema::activities::dsl::*;
         use schema::organizers;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-
-        auth::check_if_admin(&auth.0, &data.settings).or(auth::check_permissions_in_any(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &post_data.0.organizers,
-        ))?;
-
-        let new_activity = diesel::insert_into(activities)
-            .values(post_data.0.activity)
-            .returning(models::database::Activity::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
+        activity_editors(&post_data.0.organizers).authorize(&auth.0)?;
+        if post_data.0.organizers.is_empty()
+            || post_data.0.organizers.len() != post_data.0.people_in_charge.len()
+        {
+            return Err(ApiError::validation(
+                "Every organizer needs a person in charge.",
+            ));
+        }
 
+        // Media acceptance is derived from the media reviews.
+        let activity_data = models::database::NaiveActivity {
+            is_media_accepted: false,
+            ..post_data.0.activity
+        };
         let organizers = post_data
             .0
             .organizers
             .into_iter()
             .zip(post_data.0.people_in_charge);
 
-        diesel::insert_into(organizers::table)
-            .values(
-                organizers
-                    .map(|(asoc, person)| models::database::NaiveOrganizer {
-                        asociation: asoc,
-                        activity: new_activity.id,
-                        person_in_charge: person,
-                    })
-                    .collect::<Vec<models::database::NaiveOrganizer>>(),
-            )
-            .execute(conn)
-            .map_err(error::InternalServerError)?;
+        let new_activity = data
+            .transaction(move |conn| {
+                let new_activity = diesel::insert_into(activities)
+                    .values(activity_data)
+                    .returning(models::database::Activity::as_returning())
+                    .get_result(conn)?;
+
+                diesel::insert_into(organizers::table)
+                    .values(
+                        organizers
+                            .map(|(asoc, person)| models::database::NaiveOrganizer {
+                                asociation: asoc,
+                                activity: new_activity.id,
+                                person_in_charge: person,
+                            })
+                            .collect::<Vec<models::database::NaiveOrganizer>>(),
+                    )
+                    .execute(conn)?;
+
+                Ok(new_activity)
+            })
+            .await?;
 
         Ok(Json(new_activity))
     }
@@ -1014,34 +2574,34 @@ impl DanubitApi {
         use schema::asociations;
         use schema::users;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let activity = activities::table
-            .filter(activities::id.eq(activity_id.0))
-            .select(models::database::Activity::as_select())
-            .first(conn)
-            .map_err(error::InternalServerError)?;
-
-        let (asocs, people) = models::database::Organizer::belonging_to(&activity)
-            .inner_join(asociations::table)
-            .inner_join(users::table)
-            .select((
-                models::database::Organizer::as_select(),
-                models::database::Asociation::as_select(),
-                models::database::User::as_select(),
-            ))
-            .load(conn)
-            .map_err(error::InternalServerError)?
-            .into_iter()
-            .map(|(_, a, b)| (a, b))
-            .unzip();
-
-        let result = models::api::FullActivity {
-            activity,
-            organizers: asocs,
-            people_in_charge: people,
-        };
-
-        Ok(Json(result))
+        data.run(move |conn, _| {
+            let activity = activities::table
+                .filter(activities::id.eq(activity_id.0))
+                .select(models::database::Activity::as_select())
+                .first(conn)?;
+
+            let (asocs, people) = models::database::Organizer::belonging_to(&activity)
+                .inner_join(asociations::table)
+                .inner_join(users::table)
+                .select((
+                    models::database::Organizer::as_select(),
+                    models::database::Asociation::as_select(),
+                    models::database::User::as_select(),
+                ))
+                .load(conn)?
+                .into_iter()
+                .map(|(_, a, b)| (a, b))
+                .unzip();
+
+            let result = models::api::FullActivity {
+                activity,
+                organizers: asocs,
+                people_in_charge: people,
+            };
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -1054,9 +2614,115 @@ impl DanubitApi {
         activity_id: Path<i64>,
         update_data: Json<models::api::NewFullActivity>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        auth: Authorized<ActivityEditors>,
     ) -> Result<Json<models::database::Activity>> {
-        todo!()
+        use schema::activities;
+        use schema::organizers;
+
+        let update_data = update_data.0;
+        let organizer_count = update_data.organizers.len();
+        if update_data.organizers.len() != update_data.people_in_charge.len() {
+            return Err(ApiError::validation(
+                "Every organizer needs a person in charge.",
+            ));
+        }
+        let wanted: HashMap<Uuid, Uuid> = update_data
+            .organizers
+            .into_iter()
+            .zip(update_data.people_in_charge)
+            .collect();
+        if wanted.len() != organizer_count {
+            return Err(ApiError::validation("Duplicated organizer."));
+        }
+        if wanted.is_empty() {
+            return Err(ApiError::validation(
+                "An activity needs at least one organizer.",
+            ));
+        }
+
+        data.run(move |conn, _| {
+            let current = activities::table
+                .find(activity_id.0)
+                .select(models::database::Activity::as_select())
+                .first(conn)
+                .optional()?
+                .ok_or(ApiError::not_found("Activity not found"))?;
+            let current_organizers = models::database::Organizer::belonging_to(&current)
+                .select(models::database::Organizer::as_select())
+                .load(conn)?;
+            let current_asociations: Vec<Uuid> =
+                current_organizers.iter().map(|o| o.asociation).collect();
+
+            let removed: Vec<&models::database::Organizer> = current_organizers
+                .iter()
+                .filter(|o| !wanted.contains_key(&o.asociation))
+                .collect();
+            for organizer in &removed {
+                organizer_removers(&current_asociations, organizer.asociation)
+                    .authorize(&auth.0)?;
+            }
+
+            let activity = update_data.activity;
+            let rescheduled =
+                activity.initial_date != current.initial_date || activity.room != current.room;
+
+            let result = transaction(conn, |conn| {
+                let result = update(activities::table.find(current.id))
+                    .set((
+                        activities::name.eq(activity.name),
+                        activities::description.eq(activity.description),
+                        activities::room.eq(activity.room),
+                        activities::initial_date.eq(activity.initial_date),
+                        activities::is_multi_session.eq(activity.is_multi_session),
+                        activities::is_creditable.eq(activity.is_creditable),
+                        activities::is_external.eq(activity.is_external),
+                        // Approvals are never taken from the request, and a
+                        // new date or room needs them again.
+                        activities::is_accepted.eq(current.is_accepted && !rescheduled),
+                        activities::is_room_accepted.eq(current.is_room_accepted && !rescheduled),
+                        activities::is_registration_needed.eq(activity.is_registration_needed),
+                        activities::access.eq(activity.access),
+                        activities::additional_info
+                            .eq(activity.additional_info.unwrap_or(current.additional_info)),
+                    ))
+                    .returning(models::database::Activity::as_returning())
+                    .get_result(conn)?;
+
+                delete(organizers::table.filter(
+                    organizers::id.eq_any(removed.iter().map(|o| o.id).collect::<Vec<i64>>()),
+                ))
+                .execute(conn)?;
+
+                for organizer in &current_organizers {
+                    match wanted.get(&organizer.asociation) {
+                        Some(person) if *person != organizer.person_in_charge => {
+                            update(organizers::table.find(organizer.id))
+                                .set(organizers::person_in_charge.eq(person))
+                                .execute(conn)?;
+                        }
+                        _ => (),
+                    }
+                }
+
+                let added: Vec<models::database::NaiveOrganizer> = wanted
+                    .iter()
+                    .filter(|(a, _)| !current_asociations.contains(a))
+                    .map(|(a, p)| models::database::NaiveOrganizer {
+                        asociation: *a,
+                        activity: current.id,
+                        person_in_charge: *p,
+                    })
+                    .collect();
+                diesel::insert_into(organizers::table)
+                    .values(added)
+                    .execute(conn)?;
+
+                Ok(result)
+            })?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -1068,23 +2734,59 @@ impl DanubitApi {
         &self,
         activity_id: Path<i64>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
+        _auth: Authorized<ActivityOwners>,
     ) -> Result<()> {
         use schema::activities::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            delete(activities.filter(id.eq(&activity_id.0))).execute(conn)?;
 
-        auth::check_permissions_in_any(
-            &auth.0,
-            models::database::BoardStatus::Board,
-            &auth.0.board_of,
-        )?;
+            Ok(())
+        })
+        .await
+    }
 
-        delete(activities.filter(id.eq(&activity_id.0)))
-            .execute(conn)
-            .map_err(error::InternalServerError)?;
+    #[oai(
+        path = "/activities/:activity_id/reservations",
+        method = "get",
+        tag = "ApiTags::Activities"
+    )]
+    async fn list_activity_reservations(
+        &self,
+        activity_id: Path<i64>,
+        data: Data<&ServerData>,
+        _auth: Authorized<ActivityEditors>,
+    ) -> Result<Json<Vec<models::api::FullReservation>>> {
+        data.run(move |conn, _| {
+            let result = material_reservations(conn, None, Some(activity_id.0))?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
 
-        Ok(())
+    #[oai(
+        path = "/activities/:activity_id/media",
+        method = "get",
+        tag = "ApiTags::Activities"
+    )]
+    async fn list_activity_media(
+        &self,
+        activity_id: Path<i64>,
+        data: Data<&ServerData>,
+        _auth: Authorized<ActivityEditors>,
+    ) -> Result<Json<Vec<models::database::Media>>> {
+        use schema::media::dsl::*;
+
+        data.run(move |conn, _| {
+            let result = media
+                .filter(activity.eq(activity_id.0))
+                .select(models::database::Media::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -1094,12 +2796,200 @@ impl DanubitApi {
     )]
     async fn add_activity_media(
         &self,
-        asociation_id: Path<String>,
+        activity_id: Path<i64>,
         upload: models::api::MediaUpload,
         data: Data<&ServerData>,
+        _auth: Authorized<ActivityEditors>,
+    ) -> Result<Json<models::database::Media>> {
+        use schema::media::dsl::*;
+
+        let media_data = upload.file_data.0;
+        let contents =
+            uploads::read_limited(upload.upload, uploads::max_upload_size(&media_data.kind))
+                .await
+                .map_err(ApiError::bad_request)?
+                .ok_or(ApiError::payload_too_large(
+                    "File is too large for this kind of media.",
+                ))?;
+        let extension = uploads::sniff_type(&contents)
+            .filter(|t| uploads::allowed_types(&media_data.kind).contains(t))
+            .ok_or(ApiError::unsupported_media_type(
+                "File type is not allowed for this kind of media.",
+            ))?;
+
+        let media_uuid = Uuid::new_v4();
+        let storage = data.storage.clone();
+        let stored_keys = tokio::task::spawn_blocking(move || {
+            store_media(storage.as_ref(), &media_uuid, extension, &contents)
+        })
+        .await
+        .map_err(ApiError::internal)??;
+
+        let new_media = models::database::NaiveMedia {
+            name: media_data.name,
+            activity: Some(activity_id.0),
+            kind: media_data.kind,
+            path: stored_keys[0].clone(),
+        };
+        let result = data
+            .transaction(move |conn| {
+                let inserted = diesel::insert_into(media)
+                    .values(new_media)
+                    .returning(models::database::Media::as_returning())
+                    .get_result(conn)?;
+                refresh_media_acceptance(conn, activity_id.0)?;
+                Ok(inserted)
+            })
+            .await;
+        if result.is_err() {
+            let storage = data.storage.clone();
+            tokio::task::spawn_blocking(move || remove_media(storage.as_ref(), &stored_keys))
+                .await
+                .ok();
+        }
+
+        Ok(Json(result?))
+    }
+
+    #[oai(
+        path = "/activities/:activity_id/media/:media_id/content",
+        method = "get",
+        tag = "ApiTags::Activities"
+    )]
+    async fn get_activity_media_content(
+        &self,
+        activity_id: Path<i64>,
+        media_id: Path<i64>,
+        variant: Query<Option<String>>,
+        data: Data<&ServerData>,
+        _auth: Authorized<ActivityEditors>,
+    ) -> Result<Response<Binary<Vec<u8>>>> {
+        use schema::media::dsl::*;
+
+        data.run(move |conn, data| {
+            let variant = match variant.0.as_deref() {
+                None => uploads::Variant::Original,
+                Some(v) => uploads::Variant::parse(v)
+                    .ok_or(ApiError::validation("Unknown media variant."))?,
+            };
+
+            let stored_media = media
+                .filter(activity.eq(activity_id.0))
+                .filter(id.eq(media_id.0))
+                .select(models::database::Media::as_select())
+                .first(conn)?;
+
+            // Files without resized copies, like PDF posters, are served as is.
+            let variant_file = uploads::variant_keys(&stored_media.path)
+                .into_iter()
+                .filter(|(v, _)| *v == variant)
+                .map(|(_, k)| k)
+                .find_map(|k| data.storage.retrieve(&k).ok().map(|c| (k, c)));
+            let (key, contents) = match variant_file {
+                Some(file) => file,
+                None => {
+                    let contents = data
+                        .storage
+                        .retrieve(&stored_media.path)
+                        .map_err(ApiError::internal)?;
+                    (stored_media.path, contents)
+                }
+            };
+
+            Ok(Response::new(Binary(contents))
+                .header(header::CONTENT_TYPE, storage::content_type(&key)))
+        })
+        .await
+    }
+
+    #[oai(path = "/reviews/media", method = "get", tag = "ApiTags::Activities")]
+    async fn list_pending_media(
+        &self,
+        data: Data<&ServerData>,
         auth: JWTBearerAuth,
-    ) -> Result<Json<models::database::Activity>> {
-        todo!()
+    ) -> Result<Json<Vec<models::api::PendingMedia>>> {
+        use schema::activities;
+        use schema::media;
+        use schema::organizers;
+
+        data.run(move |conn, _| {
+            let mut pending = media::table
+                .inner_join(activities::table)
+                .filter(media::review_status.eq(models::database::MediaReviewStatus::Pending))
+                .order((media::activity, media::id))
+                .into_boxed();
+
+            if !Policy::Admin.allows(Some(&auth.0)) {
+                let managed_activities = organizers::table
+                    .filter(organizers::asociation.eq_any(&auth.0.manager_of))
+                    .select(organizers::activity)
+                    .load::<i64>(conn)?;
+                pending = pending.filter(media::activity.eq_any(managed_activities));
+            }
+
+            let pending: Vec<(models::database::Media, models::database::Activity)> = pending
+                .select((
+                    models::database::Media::as_select(),
+                    models::database::Activity::as_select(),
+                ))
+                .load(conn)?;
+
+            let mut result: Vec<models::api::PendingMedia> = vec![];
+            for (m, a) in pending {
+                match result.last_mut() {
+                    Some(last) if last.activity.id == a.id => last.media.push(m),
+                    _ => result.push(models::api::PendingMedia {
+                        activity: a,
+                        media: vec![m],
+                    }),
+                }
+            }
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/activities/:activity_id/media/:media_id/review",
+        method = "put",
+        tag = "ApiTags::Activities"
+    )]
+    async fn review_activity_media(
+        &self,
+        activity_id: Path<i64>,
+        media_id: Path<i64>,
+        review: Json<models::api::MediaReview>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Or<Admin, ActivityManagers>>,
+    ) -> Result<Json<models::database::Media>> {
+        use schema::media::dsl::*;
+
+        data.run(move |conn, _| {
+            let status = if review.0.is_accepted {
+                models::database::MediaReviewStatus::Accepted
+            } else {
+                models::database::MediaReviewStatus::Rejected
+            };
+            let result = transaction(conn, |conn| {
+                let reviewed = update(
+                    media
+                        .filter(activity.eq(activity_id.0))
+                        .filter(id.eq(media_id.0)),
+                )
+                .set((
+                    review_status.eq(status),
+                    review_comment.eq(review.0.comment),
+                ))
+                .returning(models::database::Media::as_returning())
+                .get_result(conn)?;
+                refresh_media_acceptance(conn, activity_id.0)?;
+                Ok(reviewed)
+            })?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -1109,12 +2999,34 @@ impl DanubitApi {
     )]
     async fn delete_activity_media(
         &self,
-        asociation_id: Path<String>,
-        media_id: Path<String>,
+        activity_id: Path<i64>,
+        media_id: Path<i64>,
         data: Data<&ServerData>,
-        auth: JWTBearerAuth,
-    ) -> Result<Json<models::database::Activity>> {
-        todo!()
+        _auth: Authorized<ActivityEditors>,
+    ) -> Result<()> {
+        use schema::media::dsl::*;
+
+        data.run(move |conn, data| {
+            let deleted = transaction(conn, |conn| {
+                let deleted = delete(
+                    media
+                        .filter(activity.eq(activity_id.0))
+                        .filter(id.eq(media_id.0)),
+                )
+                .returning(models::database::Media::as_returning())
+                .get_result(conn)?;
+                refresh_media_acceptance(conn, activity_id.0)?;
+                Ok(deleted)
+            })?;
+
+            data.storage.remove(&deleted.path).ok();
+            for (_, variant_key) in uploads::variant_keys(&deleted.path) {
+                data.storage.remove(&variant_key).ok();
+            }
+
+            Ok(())
+        })
+        .await
     }
 
     #[oai(
@@ -1129,14 +3041,15 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Registration>>> {
         use schema::registration::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = registration
-            .filter(activity.eq(&activity_id.0))
-            .select(models::database::Registration::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = registration
+                .filter(activity.eq(&activity_id.0))
+                .select(models::database::Registration::as_select())
+                .load(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
     #[oai(
         path = "/activities/:activity_id/registration",
@@ -1151,15 +3064,15 @@ impl DanubitApi {
     ) -> Result<Json<models::database::Registration>> {
         use schema::registration::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = diesel::insert_into(registration)
+                .values(post_data.0)
+                .returning(models::database::Registration::as_returning())
+                .get_result(conn)?;
 
-        let result = diesel::insert_into(registration)
-            .values(post_data.0)
-            .returning(models::database::Registration::as_returning())
-            .get_result(conn)
-            .map_err(error::InternalServerError)?;
-
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(
@@ -1175,6 +3088,162 @@ impl DanubitApi {
         todo!()
     }
 
+    #[oai(
+        path = "/users/:user_id/verification",
+        method = "post",
+        tag = "ApiTags::Users"
+    )]
+    async fn resend_user_verification(
+        &self,
+        user_id: Path<Uuid>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Admin>,
+    ) -> Result<()> {
+        use schema::users::dsl::*;
+
+        let (user, token) = data
+            .transaction(move |conn| {
+                let user = users
+                    .filter(id.eq(user_id.0))
+                    .filter(activated.eq(false))
+                    .select(models::database::User::as_select())
+                    .first(conn)?;
+                let token = auth::insert_email_verification(conn, user.id)?;
+                Ok((user, token))
+            })
+            .await?;
+
+        data.send_mail(auth::email_verification_mail(&data.settings, &user, &token))
+            .await;
+
+        Ok(())
+    }
+
+    #[oai(
+        path = "/users/:user_id/activation",
+        method = "put",
+        tag = "ApiTags::Users"
+    )]
+    async fn activate_user(
+        &self,
+        user_id: Path<Uuid>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Admin>,
+    ) -> Result<Json<models::database::User>> {
+        use schema::users::dsl::*;
+
+        data.run(move |conn, _| {
+            let result = update(users.filter(id.eq(user_id.0)))
+                .set(activated.eq(true))
+                .returning(models::database::User::as_returning())
+                .get_result(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(path = "/users/:user_id/roles", method = "get", tag = "ApiTags::Users")]
+    async fn get_user_roles(
+        &self,
+        user_id: Path<Uuid>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Admin>,
+    ) -> Result<Json<Vec<models::database::UserRole>>> {
+        use schema::user_roles;
+
+        data.run(move |conn, _| {
+            let result = user_roles::table
+                .filter(user_roles::user_id.eq(user_id.0))
+                .select(user_roles::role)
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/users/:user_id/roles/:role",
+        method = "put",
+        tag = "ApiTags::Users"
+    )]
+    async fn grant_user_role(
+        &self,
+        user_id: Path<Uuid>,
+        role: Path<models::database::UserRole>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Admin>,
+    ) -> Result<()> {
+        use schema::user_roles;
+        use schema::users;
+
+        data.run(move |conn, _| {
+            transaction(conn, |conn| {
+                diesel::insert_into(user_roles::table)
+                    .values(models::database::NaiveUserRole {
+                        user_id: user_id.0,
+                        role: role.0,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+
+                // Outstanding access tokens carry the old roles.
+                update(users::table.filter(users::id.eq(user_id.0)))
+                    .set(users::token_generation.eq(users::token_generation + 1))
+                    .execute(conn)
+            })?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/users/:user_id/roles/:role",
+        method = "delete",
+        tag = "ApiTags::Users"
+    )]
+    async fn revoke_user_role(
+        &self,
+        user_id: Path<Uuid>,
+        role: Path<models::database::UserRole>,
+        data: Data<&ServerData>,
+        _auth: Authorized<Admin>,
+    ) -> Result<()> {
+        use schema::user_roles;
+        use schema::users;
+
+        data.run(move |conn, _| {
+            if role.0 == models::database::UserRole::PlatformAdmin {
+                let admins = user_roles::table
+                    .filter(user_roles::role.eq(models::database::UserRole::PlatformAdmin))
+                    .filter(user_roles::user_id.ne(user_id.0))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if admins == 0 {
+                    return Err(ApiError::conflict("Cannot remove the last platform admin."));
+                }
+            }
+
+            transaction(conn, |conn| {
+                delete(
+                    user_roles::table
+                        .filter(user_roles::user_id.eq(user_id.0))
+                        .filter(user_roles::role.eq(role.0)),
+                )
+                .execute(conn)?;
+
+                update(users::table.filter(users::id.eq(user_id.0)))
+                    .set(users::token_generation.eq(users::token_generation + 1))
+                    .execute(conn)
+            })?;
+
+            Ok(())
+        })
+        .await
+    }
+
     #[oai(path = "/session/board_of", method = "get", tag = "ApiTags::Session")]
     async fn get_session_asociations_board(
         &self,
@@ -1183,14 +3252,35 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Asociation>>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = asociations
-            .filter(id.eq_any(auth.0.board_of))
-            .select(models::database::Asociation::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = asociations
+                .filter(id.eq_any(auth.0.board_of))
+                .select(models::database::Asociation::as_select())
+                .load(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
+    }
+
+    #[oai(path = "/session/lendings", method = "get", tag = "ApiTags::Session")]
+    async fn get_session_lendings(
+        &self,
+        data: Data<&ServerData>,
+        auth: JWTBearerAuth,
+    ) -> Result<Json<Vec<models::database::Lending>>> {
+        use schema::lendings::dsl::*;
+
+        data.run(move |conn, _| {
+            let result = lendings
+                .filter(user_id.eq(auth.0.sub))
+                .order(requested_at.desc())
+                .select(models::database::Lending::as_select())
+                .load(conn)?;
+
+            Ok(Json(result))
+        })
+        .await
     }
 
     #[oai(path = "/session/member_of", method = "get", tag = "ApiTags::Session")]
@@ -1201,13 +3291,102 @@ impl DanubitApi {
     ) -> Result<Json<Vec<models::database::Asociation>>> {
         use schema::asociations::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let result = asociations
-            .filter(id.eq_any(auth.0.member_of))
-            .select(models::database::Asociation::as_select())
-            .load(conn)
-            .map_err(error::InternalServerError)?;
+        data.run(move |conn, _| {
+            let result = asociations
+                .filter(id.eq_any(auth.0.member_of))
+                .select(models::database::Asociation::as_select())
+                .load(conn)?;
 
-        Ok(Json(result))
+            Ok(Json(result))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u8, hour: u8) -> time::PrimitiveDateTime {
+        time::Date::from_calendar_date(2026, time::Month::May, day)
+            .unwrap()
+            .with_hms(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn day(day: u8) -> time::Date {
+        at(day, 0).date()
+    }
+
+    #[test]
+    fn overlapping_reservations_add_up() {
+        let reserved = [(2, at(4, 10), at(4, 12)), (3, at(4, 11), at(4, 13))];
+        assert_eq!(peak_usage(at(4, 9), at(4, 14), &reserved, &[]), 5);
+    }
+
+    #[test]
+    fn back_to_back_reservations_do_not_add_up() {
+        let reserved = [(2, at(4, 10), at(4, 12)), (3, at(4, 12), at(4, 14))];
+        assert_eq!(peak_usage(at(4, 9), at(4, 15), &reserved, &[]), 3);
+    }
+
+    #[test]
+    fn reservations_outside_the_window_are_ignored() {
+        let reserved = [
+            (4, at(4, 8), at(4, 10)),
+            (1, at(4, 11), at(4, 12)),
+            (4, at(4, 14), at(4, 16)),
+        ];
+        assert_eq!(peak_usage(at(4, 10), at(4, 14), &reserved, &[]), 1);
+    }
+
+    #[test]
+    fn reservations_reaching_into_the_window_count() {
+        let reserved = [(2, at(4, 8), at(4, 11)), (3, at(4, 13), at(4, 16))];
+        assert_eq!(peak_usage(at(4, 10), at(4, 14), &reserved, &[]), 3);
+        assert_eq!(peak_usage(at(4, 10), at(4, 12), &reserved, &[]), 2);
+    }
+
+    #[test]
+    fn lendings_are_out_until_the_end_of_their_due_date() {
+        let lent = [(2, day(5))];
+        assert_eq!(peak_usage(at(5, 20), at(5, 22), &[], &lent), 2);
+        assert_eq!(peak_usage(at(6, 0), at(6, 2), &[], &lent), 0);
+        assert_eq!(peak_usage(at(7, 10), at(7, 12), &[], &lent), 0);
+    }
+
+    #[test]
+    fn lendings_stack_on_reservations() {
+        let reserved = [(3, at(5, 10), at(5, 12))];
+        let lent = [(2, day(5)), (1, day(4))];
+        assert_eq!(peak_usage(at(5, 9), at(5, 18), &reserved, &lent), 5);
+        assert_eq!(peak_usage(at(5, 12), at(5, 18), &reserved, &lent), 2);
+    }
+
+    #[tokio::test]
+    async fn anonymous_requests_cannot_read_activity_media() {
+        use poem_openapi::ApiExtractor;
+
+        let (req, mut body) = Request::builder()
+            .uri_str("/activities/1/media")
+            .finish()
+            .split();
+        let error =
+            Authorized::<ActivityEditors>::from_request(&req, &mut body, Default::default())
+                .await
+                .err()
+                .unwrap();
+        assert!(matches!(ApiError::from(error), ApiError::Unauthorized(_)));
+    }
+
+    #[test]
+    fn accepting_a_membership_renews_it() {
+        // Pending requests have no expiry date, so accepting one goes through
+        // the renewal penalty like extending an accepted membership does.
+        assert!(renews_membership(None, Some(day(20))));
+        assert!(renews_membership(Some(day(5)), Some(day(20))));
+        assert!(!renews_membership(Some(day(20)), Some(day(20))));
+        assert!(!renews_membership(Some(day(20)), Some(day(5))));
+        assert!(!renews_membership(Some(day(20)), None));
     }
 }
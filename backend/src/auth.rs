@@ -1,22 +1,29 @@
+use crate::api::transaction;
+use crate::error::{ApiError, Result};
+use crate::mail;
 use crate::models;
 use crate::schema;
 use crate::settings::ServerData;
 use crate::settings::Settings;
 
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
     Argon2,
 };
 use diesel::prelude::*;
-use diesel::{QueryDsl, SelectableHelper};
+use diesel::{update, QueryDsl, SelectableHelper};
 use jwt::SignWithKey;
-use poem::http::StatusCode;
-use poem::{error, web::Data, Result};
+use poem::web::Data;
 use poem_openapi::payload::PlainText;
 use poem_openapi::{payload::Json, Object, OpenApi};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
+use time::{OffsetDateTime, PrimitiveDateTime};
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -24,7 +31,9 @@ pub struct AuthScheme {
     pub sub: Uuid,
     pub iat: u64,
     pub exp: u64,
+    pub generation: i32,
     pub username: String,
+    pub roles: Vec<models::database::UserRole>,
     pub manager_of: Vec<Uuid>,
     pub chair_of: Vec<Uuid>,
     pub board_of: Vec<Uuid>,
@@ -37,6 +46,9 @@ pub struct LoginResponse {
     pub username: String,
     pub token: String,
     pub expires_at: u64,
+    pub refresh_token: String,
+    pub refresh_expires_at: u64,
+    pub roles: Vec<models::database::UserRole>,
     pub manager_of: Vec<Uuid>,
     pub chair_of: Vec<Uuid>,
     pub board_of: Vec<Uuid>,
@@ -67,6 +79,12 @@ pub struct Standin {
     email: String,
 }
 
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct Claim {
+    token: String,
+    password: String,
+}
+
 #[derive(Serialize, Deserialize, Object, Debug)]
 pub struct PasswordChange {
     email: String,
@@ -81,6 +99,32 @@ pub struct UsernameChange {
     password: String,
 }
 
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct ForgotPassword {
+    email: String,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct EmailVerification {
+    token: String,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct VerificationResend {
+    email: String,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct PasswordReset {
+    token: String,
+    new_password: String,
+}
+
 pub struct DanubitAuthApi;
 
 #[OpenApi]
@@ -91,156 +135,358 @@ impl DanubitAuthApi {
         data: Data<&ServerData>,
         post_data: Json<Login>,
     ) -> Result<Json<LoginResponse>> {
-        use schema::asociations;
-        use schema::managers;
-        use schema::members;
-        use schema::users::dsl::*;
+        data.run(move |conn, data| {
+            let user = find_user_with_password(conn, &post_data.0.email, &post_data.0.password)?;
+            if !user.activated {
+                return Err(ApiError::forbidden(
+                    "Account not activated, check your email for the verification link",
+                ));
+            }
+
+            let refresh_token = insert_refresh_token(conn, &data.settings, user.id)?;
+            let response = issue_session(conn, &data.settings, user, refresh_token)?;
+
+            Ok(Json(response))
+        })
+        .await
+    }
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let user = users
-            .filter(email.eq(post_data.0.email))
-            .select(models::database::User::as_select())
-            .first(conn)
-            .map_err(error::InternalServerError)?;
-
-        let saved_password = user.password_hash.ok_or(error::NotFoundError)?;
-        let parsed_hash = PasswordHash::new(&saved_password)
-            .map_err(|e| argon_error_to_api_error(e, StatusCode::INTERNAL_SERVER_ERROR))?;
-        Argon2::default()
-            .verify_password(post_data.0.password.as_bytes(), &parsed_hash)
-            .map_err(|e| argon_error_to_api_error(e, StatusCode::FORBIDDEN))?;
-
-        let manager_id = managers::table
-            .filter(managers::user_id.eq(&user.id))
-            .select(managers::id)
-            .first::<i64>(conn)
-            .optional()
-            .map_err(error::InternalServerError)?;
-
-        let manager_of = match manager_id {
-            None => Vec::<Uuid>::new(),
-            Some(x) => asociations::table
-                .filter(asociations::manager.eq(x))
-                .select(asociations::id)
-                .load::<Uuid>(conn)
-                .map_err(error::InternalServerError)?,
-        };
-
-        let chair_of = members::table
-            .filter(members::user_id.eq(&user.id))
-            .filter(members::board_status.eq(models::database::BoardStatus::Chair))
-            .select(members::asociation)
-            .load::<Uuid>(conn)
-            .map_err(error::InternalServerError)?;
-
-        let board_of = members::table
-            .filter(members::user_id.eq(&user.id))
-            .filter(members::board_status.eq_any(vec![
-                models::database::BoardStatus::Board,
-                models::database::BoardStatus::ViceChair,
-                models::database::BoardStatus::Chair,
-            ]))
-            .select(members::asociation)
-            .load::<Uuid>(conn)
-            .map_err(error::InternalServerError)?;
-
-        let member_of = members::table
-            .filter(members::user_id.eq(&user.id))
-            .select(members::asociation)
-            .load::<Uuid>(conn)
-            .map_err(error::InternalServerError)?;
-
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_err(error::InternalServerError)?;
-
-        let auth_scheme = AuthScheme {
-            sub: user.id,
-            iat: timestamp.as_secs(),
-            exp: timestamp.as_secs() + 60 * 60 * 24,
-            username: user.username.clone(),
-            manager_of: manager_of.clone(),
-            chair_of: chair_of.clone(),
-            board_of: board_of.clone(),
-            member_of: member_of.clone(),
-        }
-        .sign_with_key(&data.0.settings.private_key)
-        .map_err(error::InternalServerError)?;
-
-        let response = LoginResponse {
-            id: user.id,
-            username: user.username,
-            token: auth_scheme,
-            expires_at: timestamp.as_secs() + 60 * 60 * 24,
-            manager_of,
-            chair_of,
-            board_of,
-            member_of,
-        };
-
-        Ok(Json(response))
+    #[oai(path = "/refresh", method = "post")]
+    async fn refresh(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<RefreshRequest>,
+    ) -> Result<Json<LoginResponse>> {
+        use schema::refresh_tokens;
+        use schema::users;
+
+        data.run(move |conn, data| {
+            let now = now_timestamp();
+            let token_hash = hash_token(&post_data.0.refresh_token);
+
+            let session = transaction(conn, |conn| {
+                let token = refresh_tokens::table
+                    .filter(refresh_tokens::token_hash.eq(token_hash))
+                    .select((
+                        refresh_tokens::id,
+                        refresh_tokens::user_id,
+                        refresh_tokens::expires_at,
+                        refresh_tokens::revoked_at,
+                    ))
+                    .first::<(i64, Uuid, PrimitiveDateTime, Option<PrimitiveDateTime>)>(conn)
+                    .optional()?;
+                let Some((token_id, token_user, expires_at, revoked_at)) = token else {
+                    return Ok(None);
+                };
+
+                // A rotated token being used again means it leaked, so every
+                // session of the user is closed.
+                if revoked_at.is_some() {
+                    revoke_refresh_tokens(conn, token_user)?;
+                    return Ok(None);
+                }
+                if expires_at <= now {
+                    return Ok(None);
+                }
+
+                let rotated = update(
+                    refresh_tokens::table
+                        .filter(refresh_tokens::id.eq(token_id))
+                        .filter(refresh_tokens::revoked_at.is_null()),
+                )
+                .set(refresh_tokens::revoked_at.eq(now))
+                .execute(conn)?;
+                if rotated == 0 {
+                    return Ok(None);
+                }
+
+                let user = users::table
+                    .filter(users::id.eq(token_user))
+                    .filter(users::activated.eq(true))
+                    .select(models::database::User::as_select())
+                    .first(conn)
+                    .optional()?;
+                let Some(user) = user else {
+                    return Ok(None);
+                };
+                let refresh_token = insert_refresh_token(conn, &data.settings, user.id)?;
+                Ok(Some((user, refresh_token)))
+            })?;
+            let (user, refresh_token) =
+                session.ok_or_else(|| ApiError::unauthorized("Invalid refresh token"))?;
+
+            let response = issue_session(conn, &data.settings, user, refresh_token)?;
+
+            Ok(Json(response))
+        })
+        .await
+    }
+
+    #[oai(path = "/logout", method = "post")]
+    async fn logout(&self, data: Data<&ServerData>, post_data: Json<RefreshRequest>) -> Result<()> {
+        use schema::refresh_tokens::dsl::*;
+
+        data.run(move |conn, _| {
+            update(
+                refresh_tokens
+                    .filter(token_hash.eq(hash_token(&post_data.0.refresh_token)))
+                    .filter(revoked_at.is_null()),
+            )
+            .set(revoked_at.eq(now_timestamp()))
+            .execute(conn)?;
+
+            Ok(())
+        })
+        .await
     }
 
     #[oai(path = "/signup", method = "post")]
     async fn signup(&self, data: Data<&ServerData>, post_data: Json<UserSignup>) -> Result<()> {
         use schema::users::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
-        let salt = SaltString::generate(&mut OsRng);
-        let hashed_password = Argon2::default()
-            .hash_password(post_data.0.password.as_bytes(), &salt)
-            .map_err(|e| argon_error_to_api_error(e, StatusCode::INTERNAL_SERVER_ERROR))?
-            .to_string();
-        println!("{hashed_password:?}");
-        let user = models::database::NaiveUser {
-            username: post_data.0.username,
-            name: post_data.0.name,
-            surname: post_data.0.surname,
-            email: post_data.0.email,
-            activated: true,
-            password_hash: Some(hashed_password),
-            additional_info: post_data.0.additional_info,
-        };
-        println!("{user:?}");
-        diesel::insert_into(users)
-            .values(user)
-            .execute(conn)
-            .map_err(error::InternalServerError)?;
+        let hashed_password = hash_password(&post_data.0.password)?;
+        // The account and its token are committed together, and the mail only
+        // goes out afterwards, so a failed send never leaves a half-created
+        // account behind.
+        let (user, token, is_standin) = data
+            .transaction(move |conn| {
+                // People registered at a stand already have an account, so
+                // they get a new invitation to claim it instead of a duplicate.
+                let standin = users
+                    .filter(email.eq(&post_data.0.email))
+                    .filter(password_hash.is_null())
+                    .select(models::database::User::as_select())
+                    .first(conn)
+                    .optional()?;
+                if let Some(standin) = standin {
+                    let token = insert_invitation(conn, standin.id)?;
+                    return Ok((standin, token, true));
+                }
+
+                let user = diesel::insert_into(users)
+                    .values(models::database::NaiveUser {
+                        username: post_data.0.username,
+                        name: post_data.0.name,
+                        surname: post_data.0.surname,
+                        email: post_data.0.email,
+                        activated: false,
+                        password_hash: Some(hashed_password),
+                        additional_info: post_data.0.additional_info,
+                    })
+                    .returning(models::database::User::as_returning())
+                    .get_result(conn)?;
+                let token = insert_email_verification(conn, user.id)?;
+                Ok((user, token, false))
+            })
+            .await?;
+
+        if is_standin {
+            data.send_mail(invitation_mail(&data.settings, &user, &token))
+                .await;
+            return Err(ApiError::conflict(
+                "This email was registered at a stand, check it for a link to claim the account",
+            ));
+        }
+        data.send_mail(email_verification_mail(&data.settings, &user, &token))
+            .await;
 
         Ok(())
     }
 
-    #[oai(path = "/standin", method = "post")]
-    async fn standin(&self, data: Data<&ServerData>, post_data: Json<Standin>) -> Result<()> {
+    #[oai(path = "/verify_email", method = "post")]
+    async fn verify_email(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<EmailVerification>,
+    ) -> Result<PlainText<String>> {
+        use schema::email_verifications;
+        use schema::users;
+
+        let now = now_timestamp();
+        data.transaction(move |conn| {
+            let verified_user = email_verifications::table
+                .filter(email_verifications::token_hash.eq(hash_token(&post_data.0.token)))
+                .filter(email_verifications::used_at.is_null())
+                .filter(email_verifications::expires_at.gt(now))
+                .select(email_verifications::user_id)
+                .first::<Uuid>(conn)?;
+
+            update(
+                email_verifications::table
+                    .filter(email_verifications::user_id.eq(verified_user))
+                    .filter(email_verifications::used_at.is_null()),
+            )
+            .set(email_verifications::used_at.eq(now))
+            .execute(conn)?;
+
+            update(users::table.filter(users::id.eq(verified_user)))
+                .set(users::activated.eq(true))
+                .execute(conn)
+        })
+        .await
+        .map_err(|e| match e {
+            ApiError::NotFound(_) => ApiError::validation("Invalid or expired verification token"),
+            e => e,
+        })?;
+
+        Ok(PlainText("Account activated.".to_string()))
+    }
+
+    #[oai(path = "/resend_verification", method = "post")]
+    async fn resend_verification(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<VerificationResend>,
+    ) -> Result<PlainText<String>> {
         use schema::users::dsl::*;
 
-        let conn = &mut data.data_pool.get().map_err(error::InternalServerError)?;
+        let pending = data
+            .transaction(move |conn| {
+                let user = users
+                    .filter(email.eq(&post_data.0.email))
+                    .filter(activated.eq(false))
+                    .select(models::database::User::as_select())
+                    .first(conn)
+                    .optional()?;
+                match user {
+                    Some(user) => {
+                        let token = insert_email_verification(conn, user.id)?;
+                        Ok(Some((user, token)))
+                    }
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        // The answer is the same whether the account exists or not.
+        if let Some((user, token)) = pending {
+            data.send_mail(email_verification_mail(&data.settings, &user, &token))
+                .await;
+        }
 
-        let user = models::database::NaiveUser {
-            username: post_data.0.username,
-            name: post_data.0.name,
-            surname: post_data.0.surname,
-            email: post_data.0.email,
-            activated: true,
-            password_hash: None,
-            additional_info: None,
-        };
+        Ok(PlainText(
+            "If the account is pending activation, a new link has been sent.".to_string(),
+        ))
+    }
+
+    #[oai(path = "/standin", method = "post")]
+    async fn standin(&self, data: Data<&ServerData>, post_data: Json<Standin>) -> Result<()> {
+        use schema::users::dsl::*;
 
-        diesel::insert_into(users)
-            .values(user)
-            .execute(conn)
-            .map_err(error::InternalServerError)?;
+        let (user, token) = data
+            .transaction(move |conn| {
+                let user = diesel::insert_into(users)
+                    .values(models::database::NaiveUser {
+                        username: post_data.0.username,
+                        name: post_data.0.name,
+                        surname: post_data.0.surname,
+                        email: post_data.0.email,
+                        activated: true,
+                        password_hash: None,
+                        additional_info: None,
+                    })
+                    .returning(models::database::User::as_returning())
+                    .get_result(conn)?;
+                let token = insert_invitation(conn, user.id)?;
+                Ok((user, token))
+            })
+            .await?;
+
+        data.send_mail(invitation_mail(&data.settings, &user, &token))
+            .await;
 
         Ok(())
     }
 
+    #[oai(path = "/claim", method = "post")]
+    async fn claim(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<Claim>,
+    ) -> Result<PlainText<String>> {
+        use schema::invitations;
+        use schema::users;
+
+        let hashed_password = hash_password(&post_data.0.password)?;
+        let now = now_timestamp();
+        data.transaction(move |conn| {
+            let standin = invitations::table
+                .filter(invitations::token_hash.eq(hash_token(&post_data.0.token)))
+                .filter(invitations::used_at.is_null())
+                .filter(invitations::expires_at.gt(now))
+                .select(invitations::user_id)
+                .first::<Uuid>(conn)?;
+
+            update(
+                invitations::table
+                    .filter(invitations::user_id.eq(standin))
+                    .filter(invitations::used_at.is_null()),
+            )
+            .set(invitations::used_at.eq(now))
+            .execute(conn)?;
+
+            // Only the password is set, so memberships and organizer
+            // assignments made for the stand-in stay with the account.
+            update(
+                users::table
+                    .filter(users::id.eq(standin))
+                    .filter(users::password_hash.is_null()),
+            )
+            .set((
+                users::password_hash.eq(hashed_password),
+                users::activated.eq(true),
+                users::token_generation.eq(users::token_generation + 1),
+            ))
+            .returning(models::database::User::as_returning())
+            .get_result(conn)
+        })
+        .await
+        .map_err(|e| match e {
+            ApiError::NotFound(_) => ApiError::validation("Invalid or expired invitation"),
+            e => e,
+        })?;
+
+        Ok(PlainText(
+            "Account claimed, you can now log in.".to_string(),
+        ))
+    }
+
     #[oai(path = "/change_username", method = "post")]
     async fn change_username(
         &self,
         data: Data<&ServerData>,
         post_data: Json<UsernameChange>,
     ) -> Result<PlainText<String>> {
-        todo!()
+        use schema::users::dsl::*;
+
+        data.run(move |conn, _| {
+            let user = find_user_with_password(conn, &post_data.0.email, &post_data.0.password)?;
+
+            let taken = users
+                .filter(username.eq(&post_data.0.new_username))
+                .filter(id.ne(user.id))
+                .count()
+                .get_result::<i64>(conn)?;
+            if taken > 0 {
+                return Err(ApiError::conflict("Username is already taken"));
+            }
+
+            transaction(conn, |conn| {
+                update(users.filter(id.eq(user.id)))
+                    .set((
+                        username.eq(&post_data.0.new_username),
+                        token_generation.eq(token_generation + 1),
+                    ))
+                    .execute(conn)?;
+                revoke_refresh_tokens(conn, user.id)
+            })?;
+
+            Ok(PlainText(format!(
+                "Username changed to {}. Please log in again.",
+                post_data.0.new_username
+            )))
+        })
+        .await
     }
 
     #[oai(path = "/change_password", method = "post")]
@@ -249,73 +495,386 @@ impl DanubitAuthApi {
         data: Data<&ServerData>,
         post_data: Json<PasswordChange>,
     ) -> Result<PlainText<String>> {
-        todo!()
+        use schema::users::dsl::*;
+
+        data.run(move |conn, _| {
+            let user =
+                find_user_with_password(conn, &post_data.0.email, &post_data.0.old_password)?;
+            let hashed_password = hash_password(&post_data.0.new_password)?;
+
+            transaction(conn, |conn| {
+                update(users.filter(id.eq(user.id)))
+                    .set((
+                        password_hash.eq(hashed_password),
+                        token_generation.eq(token_generation + 1),
+                    ))
+                    .execute(conn)?;
+                revoke_refresh_tokens(conn, user.id)
+            })?;
+
+            Ok(PlainText(
+                "Password changed. Please log in again.".to_string(),
+            ))
+        })
+        .await
+    }
+
+    #[oai(path = "/forgot_password", method = "post")]
+    async fn forgot_password(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<ForgotPassword>,
+    ) -> Result<PlainText<String>> {
+        use schema::password_resets;
+        use schema::users;
+
+        let to = post_data.0.email.clone();
+        let token = data
+            .transaction(move |conn| {
+                let user_id = users::table
+                    .filter(users::email.eq(&post_data.0.email))
+                    .select(users::id)
+                    .first::<Uuid>(conn)
+                    .optional()?;
+                let Some(user_id) = user_id else {
+                    return Ok(None);
+                };
+
+                let (token, token_hash) = generate_token();
+                diesel::insert_into(password_resets::table)
+                    .values(models::database::NaivePasswordReset {
+                        user_id,
+                        token_hash,
+                        expires_at: now_timestamp() + time::Duration::hours(1),
+                    })
+                    .execute(conn)?;
+                Ok(Some(token))
+            })
+            .await?;
+
+        // The answer is the same whether the account exists or not.
+        if let Some(token) = token {
+            data.send_mail(mail::Mail {
+                to,
+                cc: vec![],
+                subject: "Reset your Danubit password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password of your Danubit account.\n\n\
+                    Use this link within the next hour to choose a new one:\n\
+                    {}/reset_password?token={}\n\n\
+                    If it was not you, you can ignore this message.",
+                    data.settings.frontend_url, token
+                ),
+            })
+            .await;
+        }
+
+        Ok(PlainText(
+            "If the account exists, a reset link has been sent to its email.".to_string(),
+        ))
+    }
+
+    #[oai(path = "/reset_password", method = "post")]
+    async fn reset_password(
+        &self,
+        data: Data<&ServerData>,
+        post_data: Json<PasswordReset>,
+    ) -> Result<PlainText<String>> {
+        use schema::password_resets;
+        use schema::users;
+
+        let hashed_password = hash_password(&post_data.0.new_password)?;
+        let now = now_timestamp();
+        data.transaction(move |conn| {
+            let reset_user = password_resets::table
+                .filter(password_resets::token_hash.eq(hash_token(&post_data.0.token)))
+                .filter(password_resets::used_at.is_null())
+                .filter(password_resets::expires_at.gt(now))
+                .select(password_resets::user_id)
+                .first::<Uuid>(conn)?;
+
+            update(
+                password_resets::table
+                    .filter(password_resets::user_id.eq(reset_user))
+                    .filter(password_resets::used_at.is_null()),
+            )
+            .set(password_resets::used_at.eq(now))
+            .execute(conn)?;
+
+            update(users::table.filter(users::id.eq(reset_user)))
+                .set((
+                    users::password_hash.eq(hashed_password),
+                    users::token_generation.eq(users::token_generation + 1),
+                ))
+                .execute(conn)?;
+
+            revoke_refresh_tokens(conn, reset_user)
+        })
+        .await
+        .map_err(|e| match e {
+            ApiError::NotFound(_) => ApiError::validation("Invalid or expired reset token"),
+            e => e,
+        })?;
+
+        Ok(PlainText(
+            "Password changed. Please log in again.".to_string(),
+        ))
     }
 }
 
-fn argon_error_to_api_error(argon_error: impl ToString, status_code: StatusCode) -> error::Error {
-    let error_string = argon_error.to_string();
-    error::Error::from_string(error_string, status_code)
+pub fn insert_email_verification(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<String> {
+    use schema::email_verifications;
+
+    let (token, token_hash) = generate_token();
+    let verification = models::database::NaiveEmailVerification {
+        user_id,
+        token_hash,
+        expires_at: now_timestamp() + time::Duration::days(2),
+    };
+    diesel::insert_into(email_verifications::table)
+        .values(verification)
+        .execute(conn)?;
+    Ok(token)
 }
 
-pub fn check_permissions(
-    auth_scheme: &AuthScheme,
-    min_needed_permision: models::database::BoardStatus,
-    asociation: &Uuid,
-) -> Result<(), error::Error> {
-    if (min_needed_permision <= models::database::BoardStatus::False
-        && auth_scheme.member_of.contains(asociation))
-        || (auth_scheme.board_of.contains(asociation)
-            && min_needed_permision <= models::database::BoardStatus::Board)
-        || (auth_scheme.chair_of.contains(asociation)
-            && min_needed_permision <= models::database::BoardStatus::Chair)
-    {
-        return Ok(());
+pub fn email_verification_mail(
+    settings: &Settings,
+    user: &models::database::User,
+    token: &str,
+) -> mail::Mail {
+    mail::Mail {
+        to: user.email.clone(),
+        cc: vec![],
+        subject: "Verify your Danubit account".to_string(),
+        body: format!(
+            "Welcome to Danubit, {}!\n\n\
+            Confirm this is your email address by opening this link \
+            within the next two days:\n\
+            {}/verify_email?token={}\n\n\
+            If you did not sign up, you can ignore this message.",
+            user.name, settings.frontend_url, token
+        ),
+    }
+}
+
+fn insert_invitation(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<String> {
+    use schema::invitations;
+
+    let (token, token_hash) = generate_token();
+    let invitation = models::database::NaiveInvitation {
+        user_id,
+        token_hash,
+        expires_at: now_timestamp() + time::Duration::days(14),
     };
-    Err(error::Error::from_string(
-        "Insufficient permissions",
-        StatusCode::FORBIDDEN,
-    ))
+    diesel::insert_into(invitations::table)
+        .values(invitation)
+        .execute(conn)?;
+    Ok(token)
 }
 
-pub fn check_if_manager(auth_scheme: &AuthScheme, asociation: &Uuid) -> Result<(), error::Error> {
-    if auth_scheme.manager_of.contains(asociation) {
-        return Ok(());
+fn invitation_mail(settings: &Settings, user: &models::database::User, token: &str) -> mail::Mail {
+    mail::Mail {
+        to: user.email.clone(),
+        cc: vec![],
+        subject: "Claim your Danubit account".to_string(),
+        body: format!(
+            "Hi {}!\n\n\
+            An account was created for you when you signed up at an \
+            asociation stand. Choose a password within the next two weeks \
+            to start using it:\n\
+            {}/claim?token={}\n\n\
+            If you did not sign up anywhere, you can ignore this message.",
+            user.name, settings.frontend_url, token
+        ),
     }
-    Err(error::Error::from_string(
-        "Not a manager of this asociation",
-        StatusCode::FORBIDDEN,
-    ))
 }
 
-pub fn check_if_admin(auth_scheme: &AuthScheme, settings: &Settings) -> Result<(), error::Error> {
-    if auth_scheme.username == settings.admin_username {
-        return Ok(());
+pub fn now_timestamp() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(now.date(), now.time())
+}
+
+fn generate_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    let token_hash = hash_token(&token);
+    (token, token_hash)
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Stores a new refresh token for `user` and returns it.
+fn insert_refresh_token(
+    conn: &mut PgConnection,
+    settings: &Settings,
+    user: Uuid,
+) -> QueryResult<String> {
+    use schema::refresh_tokens;
+
+    let (refresh_token, token_hash) = generate_token();
+    diesel::insert_into(refresh_tokens::table)
+        .values(models::database::NaiveRefreshToken {
+            user_id: user,
+            token_hash,
+            expires_at: now_timestamp()
+                + time::Duration::seconds(settings.refresh_token_lifetime as i64),
+        })
+        .execute(conn)?;
+    Ok(refresh_token)
+}
+
+fn issue_session(
+    conn: &mut PgConnection,
+    settings: &Settings,
+    user: models::database::User,
+    refresh_token: String,
+) -> Result<LoginResponse> {
+    use schema::asociations;
+    use schema::managers;
+    use schema::members;
+    use schema::user_roles;
+
+    let roles = user_roles::table
+        .filter(user_roles::user_id.eq(&user.id))
+        .select(user_roles::role)
+        .load::<models::database::UserRole>(conn)?;
+
+    let manager_id = managers::table
+        .filter(managers::user_id.eq(&user.id))
+        .select(managers::id)
+        .first::<i64>(conn)
+        .optional()?;
+
+    let manager_of = match manager_id {
+        None => Vec::<Uuid>::new(),
+        Some(x) => asociations::table
+            .filter(asociations::manager.eq(x))
+            .select(asociations::id)
+            .load::<Uuid>(conn)?,
+    };
+
+    let chair_of = members::table
+        .filter(members::user_id.eq(&user.id))
+        .filter(members::board_status.eq(models::database::BoardStatus::Chair))
+        .select(members::asociation)
+        .load::<Uuid>(conn)?;
+
+    let board_of = members::table
+        .filter(members::user_id.eq(&user.id))
+        .filter(members::board_status.eq_any(vec![
+            models::database::BoardStatus::Board,
+            models::database::BoardStatus::ViceChair,
+            models::database::BoardStatus::Chair,
+        ]))
+        .select(members::asociation)
+        .load::<Uuid>(conn)?;
+
+    let member_of = members::table
+        .filter(members::user_id.eq(&user.id))
+        .filter(members::is_accepted.eq(true))
+        .select(members::asociation)
+        .load::<Uuid>(conn)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(ApiError::internal)?;
+
+    let auth_scheme = AuthScheme {
+        sub: user.id,
+        iat: timestamp.as_secs(),
+        exp: timestamp.as_secs() + settings.access_token_lifetime,
+        generation: user.token_generation,
+        username: user.username.clone(),
+        roles: roles.clone(),
+        manager_of: manager_of.clone(),
+        chair_of: chair_of.clone(),
+        board_of: board_of.clone(),
+        member_of: member_of.clone(),
     }
-    Err(error::Error::from_string(
-        "Missing admin priviledges",
-        StatusCode::FORBIDDEN,
-    ))
+    .sign_with_key(&settings.private_key)
+    .map_err(ApiError::internal)?;
+
+    Ok(LoginResponse {
+        id: user.id,
+        username: user.username,
+        token: auth_scheme,
+        expires_at: timestamp.as_secs() + settings.access_token_lifetime,
+        refresh_token,
+        refresh_expires_at: timestamp.as_secs() + settings.refresh_token_lifetime,
+        roles,
+        manager_of,
+        chair_of,
+        board_of,
+        member_of,
+    })
+}
+
+pub fn revoke_refresh_tokens(conn: &mut PgConnection, user: Uuid) -> QueryResult<usize> {
+    use schema::refresh_tokens::dsl::*;
+
+    update(
+        refresh_tokens
+            .filter(user_id.eq(user))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(now_timestamp()))
+    .execute(conn)
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(ApiError::internal)?
+        .to_string())
 }
 
-pub fn check_permissions_in_any(
-    auth_scheme: &AuthScheme,
-    min_needed_permision: models::database::BoardStatus,
-    asociations: &Vec<Uuid>,
-) -> Result<(), error::Error> {
-    for uuid in asociations {
-        if (min_needed_permision <= models::database::BoardStatus::False
-            && auth_scheme.member_of.contains(uuid))
-            || (auth_scheme.board_of.contains(uuid)
-                && min_needed_permision <= models::database::BoardStatus::Board)
-            || (auth_scheme.chair_of.contains(uuid)
-                && min_needed_permision <= models::database::BoardStatus::Chair)
-        {
-            return Ok(());
-        };
+fn find_user_with_password(
+    conn: &mut PgConnection,
+    user_email: &str,
+    password: &str,
+) -> Result<models::database::User> {
+    use schema::users::dsl::*;
+
+    let user = users
+        .filter(email.eq(user_email))
+        .select(models::database::User::as_select())
+        .first(conn)?;
+
+    let saved_password = user
+        .password_hash
+        .as_ref()
+        .ok_or(ApiError::not_found("User has no password set"))?;
+    let parsed_hash = PasswordHash::new(saved_password).map_err(ApiError::internal)?;
+    Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .map_err(|e| ApiError::forbidden(e.to_string()))?;
+
+    Ok(user)
+}
+
+pub fn check_session(conn: &mut PgConnection, auth_scheme: &AuthScheme) -> Result<()> {
+    use schema::users::dsl::*;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(ApiError::internal)?;
+    if auth_scheme.exp <= timestamp.as_secs() {
+        return Err(ApiError::unauthorized("Session expired"));
+    }
+
+    let current_generation = users
+        .filter(id.eq(auth_scheme.sub))
+        .select(token_generation)
+        .first::<i32>(conn)?;
+    if current_generation != auth_scheme.generation {
+        return Err(ApiError::unauthorized("Session revoked"));
     }
-    Err(error::Error::from_string(
-        "Insufficient permissions",
-        StatusCode::FORBIDDEN,
-    ))
+    Ok(())
 }
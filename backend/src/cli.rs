@@ -0,0 +1,570 @@
+use crate::auth;
+use crate::migrations;
+use crate::models;
+use crate::schema;
+use crate::settings::Settings;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use clap::{Parser, Subcommand, ValueEnum};
+use diesel::prelude::*;
+use diesel::update;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(version, about = "Danubit backend")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (the default)
+    Serve,
+    /// Manage the database schema
+    Migrate {
+        #[arg(value_enum, default_value_t = MigrateAction::Up)]
+        action: MigrateAction,
+    },
+    /// Create an activated user
+    CreateUser {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "")]
+        name: String,
+        #[arg(long, default_value = "")]
+        surname: String,
+        /// A random password is generated and printed when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Give a user the platform admin role
+    GrantAdmin {
+        /// Username or email of the user
+        user: String,
+    },
+    /// Create an asociation
+    CreateAsociation {
+        #[arg(long)]
+        short_name: String,
+        #[arg(long)]
+        long_name: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "")]
+        description: String,
+        #[arg(long)]
+        public_joinable: bool,
+    },
+    /// Make a user the manager responsible for an asociation
+    AssignManager {
+        /// Username or email of the user
+        user: String,
+        /// Short name of the asociation
+        asociation: String,
+        /// Defaults to the user's full name when the user is not a manager yet
+        #[arg(long)]
+        name: Option<String>,
+        /// Defaults to the user's email when the user is not a manager yet
+        #[arg(long)]
+        contact_email: Option<String>,
+        #[arg(long)]
+        material_email: Option<String>,
+    },
+    /// Load users, asociations, boards and activities from a JSON seed file
+    ImportSeed { file: PathBuf },
+    /// Set a new password and revoke every session of a user
+    ResetPassword {
+        /// Username or email of the user
+        user: String,
+        /// A random password is generated and printed when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum MigrateAction {
+    /// Apply every pending migration
+    Up,
+    /// Revert the last applied migration
+    Down,
+    /// List migrations and whether they are applied
+    Status,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Seed {
+    users: Vec<SeedUser>,
+    asociations: Vec<models::database::NaiveAsociation>,
+    managers: Vec<SeedManager>,
+    board: Vec<SeedBoardMember>,
+    activities: Vec<SeedActivity>,
+}
+
+#[derive(Deserialize)]
+struct SeedUser {
+    username: String,
+    email: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    surname: String,
+    password: String,
+    #[serde(default)]
+    is_admin: bool,
+}
+
+#[derive(Deserialize)]
+struct SeedManager {
+    user: String,
+    name: String,
+    contact_email: String,
+    material_email: Option<String>,
+    asociations: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SeedBoardMember {
+    user: String,
+    asociation: String,
+    board_status: models::database::BoardStatus,
+}
+
+/// Activities are seeded already accepted, dated relative to the import so
+/// that a seed file keeps producing upcoming activities.
+#[derive(Deserialize)]
+struct SeedActivity {
+    name: String,
+    description: String,
+    room: String,
+    days_from_now: i64,
+    hour: u8,
+    #[serde(default)]
+    is_registration_needed: bool,
+    access: models::database::ActivityAccess,
+    organizers: Vec<String>,
+    people_in_charge: Vec<String>,
+}
+
+/// Runs every subcommand except `serve`, which needs the async runtime.
+pub fn run(settings: &Settings, command: Command) -> Result<(), String> {
+    let conn = &mut migrations::connect(&settings.database_url)?;
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Migrate { action } => migrate(conn, action),
+        Command::CreateUser {
+            username,
+            email,
+            name,
+            surname,
+            password,
+        } => {
+            let password = password_or_generated(password);
+            let user = create_user(conn, username, email, name, surname, &password)?;
+            println!("Created user {} ({})", user.username, user.id);
+            Ok(())
+        }
+        Command::GrantAdmin { user } => {
+            let user = find_user(conn, &user)?;
+            grant_admin(conn, user.id)?;
+            println!("{} is now a platform admin", user.username);
+            Ok(())
+        }
+        Command::CreateAsociation {
+            short_name,
+            long_name,
+            email,
+            description,
+            public_joinable,
+        } => {
+            let asociation = diesel::insert_into(schema::asociations::table)
+                .values(models::database::NaiveAsociation {
+                    short_name,
+                    long_name,
+                    email,
+                    description,
+                    is_public_joinable: public_joinable,
+                    info: None,
+                    manager: None,
+                    logo: None,
+                })
+                .returning(models::database::Asociation::as_returning())
+                .get_result(conn)
+                .map_err(|x| x.to_string())?;
+            println!(
+                "Created asociation {} ({})",
+                asociation.short_name, asociation.id
+            );
+            Ok(())
+        }
+        Command::AssignManager {
+            user,
+            asociation,
+            name,
+            contact_email,
+            material_email,
+        } => {
+            let user = find_user(conn, &user)?;
+            let manager = find_or_create_manager(
+                conn,
+                &user,
+                name.unwrap_or(format!("{} {}", user.name, user.surname)),
+                contact_email.unwrap_or(user.email.clone()),
+                material_email,
+            )?;
+            assign_manager(conn, manager, &asociation)?;
+            println!("{} now manages {asociation}", user.username);
+            Ok(())
+        }
+        Command::ImportSeed { file } => {
+            let contents = fs::read_to_string(file).map_err(|x| x.to_string())?;
+            let seed = serde_json::from_str::<Seed>(&contents).map_err(|x| x.to_string())?;
+            let mut failure = None;
+            conn.transaction(|conn| {
+                import_seed(conn, seed).map_err(|e| {
+                    failure = Some(e);
+                    diesel::result::Error::RollbackTransaction
+                })
+            })
+            .map_err(|e| failure.unwrap_or(e.to_string()))
+        }
+        Command::ResetPassword { user, password } => {
+            let user = find_user(conn, &user)?;
+            let password = password_or_generated(password);
+            reset_password(conn, user.id, &password)?;
+            println!("Password of {} reset", user.username);
+            Ok(())
+        }
+    }
+}
+
+fn migrate(conn: &mut PgConnection, action: MigrateAction) -> Result<(), String> {
+    match action {
+        MigrateAction::Up => {
+            let applied = migrations::run_pending(conn)?;
+            if applied.is_empty() {
+                println!("No pending migrations");
+            }
+            for version in applied {
+                println!("Applied {version}");
+            }
+        }
+        MigrateAction::Down => println!("Reverted {}", migrations::revert_last(conn)?),
+        MigrateAction::Status => {
+            for (name, is_applied) in migrations::status(conn)? {
+                println!("[{}] {name}", if is_applied { "X" } else { " " });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn password_or_generated(password: Option<String>) -> String {
+    password.unwrap_or_else(|| {
+        let mut bytes = [0u8; 12];
+        OsRng.fill_bytes(&mut bytes);
+        let password = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        println!("Generated password: {password}");
+        password
+    })
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    auth::hash_password(password).map_err(|e| e.body().message.clone())
+}
+
+/// Looks a user up by username or email.
+pub fn find_user(conn: &mut PgConnection, user: &str) -> Result<models::database::User, String> {
+    use schema::users::dsl::*;
+
+    users
+        .filter(username.eq(user).or(email.eq(user)))
+        .select(models::database::User::as_select())
+        .first(conn)
+        .optional()
+        .map_err(|x| x.to_string())?
+        .ok_or(format!("No user named {user}"))
+}
+
+fn find_asociation(
+    conn: &mut PgConnection,
+    asociation: &str,
+) -> Result<models::database::Asociation, String> {
+    use schema::asociations::dsl::*;
+
+    asociations
+        .filter(short_name.eq(asociation))
+        .select(models::database::Asociation::as_select())
+        .first(conn)
+        .optional()
+        .map_err(|x| x.to_string())?
+        .ok_or(format!("No asociation named {asociation}"))
+}
+
+pub fn create_user(
+    conn: &mut PgConnection,
+    username: String,
+    email: String,
+    name: String,
+    surname: String,
+    password: &str,
+) -> Result<models::database::User, String> {
+    diesel::insert_into(schema::users::table)
+        .values(models::database::NaiveUser {
+            username,
+            name,
+            surname,
+            email,
+            activated: true,
+            password_hash: Some(hash_password(password)?),
+            additional_info: None,
+        })
+        .returning(models::database::User::as_returning())
+        .get_result(conn)
+        .map_err(|x| x.to_string())
+}
+
+pub fn grant_admin(conn: &mut PgConnection, user: Uuid) -> Result<(), String> {
+    use schema::user_roles;
+    use schema::users;
+
+    let granted = diesel::insert_into(user_roles::table)
+        .values(models::database::NaiveUserRole {
+            user_id: user,
+            role: models::database::UserRole::PlatformAdmin,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map_err(|x| x.to_string())?;
+    if granted == 0 {
+        return Ok(());
+    }
+
+    // Outstanding access tokens carry the old roles.
+    update(users::table.find(user))
+        .set(users::token_generation.eq(users::token_generation + 1))
+        .execute(conn)
+        .map_err(|x| x.to_string())?;
+    Ok(())
+}
+
+fn find_or_create_manager(
+    conn: &mut PgConnection,
+    user: &models::database::User,
+    name: String,
+    contact_email: String,
+    material_email: Option<String>,
+) -> Result<i64, String> {
+    use schema::managers;
+
+    let existing = managers::table
+        .filter(managers::user_id.eq(user.id))
+        .select(managers::id)
+        .first::<i64>(conn)
+        .optional()
+        .map_err(|x| x.to_string())?;
+    if let Some(manager) = existing {
+        return Ok(manager);
+    }
+
+    diesel::insert_into(managers::table)
+        .values(models::database::NaiveManager {
+            user_id: user.id,
+            name,
+            contact_email,
+            admin_email: None,
+            material_email,
+            print_email: None,
+            comms_email: None,
+        })
+        .returning(managers::id)
+        .get_result(conn)
+        .map_err(|x| x.to_string())
+}
+
+fn assign_manager(
+    conn: &mut PgConnection,
+    manager_id: i64,
+    asociation: &str,
+) -> Result<(), String> {
+    use schema::asociations;
+
+    let asociation = find_asociation(conn, asociation)?;
+    update(asociations::table.find(asociation.id))
+        .set(asociations::manager.eq(manager_id))
+        .execute(conn)
+        .map_err(|x| x.to_string())?;
+    Ok(())
+}
+
+fn reset_password(conn: &mut PgConnection, user: Uuid, password: &str) -> Result<(), String> {
+    use schema::users;
+
+    let hashed_password = hash_password(password)?;
+    conn.transaction(|conn| {
+        update(users::table.find(user))
+            .set((
+                users::password_hash.eq(hashed_password),
+                users::token_generation.eq(users::token_generation + 1),
+            ))
+            .execute(conn)?;
+        auth::revoke_refresh_tokens(conn, user)
+    })
+    .map_err(|x| x.to_string())?;
+    Ok(())
+}
+
+/// Seeds are applied in a single transaction and skip users, asociations and
+/// activities that already exist, so a partially seeded database can be
+/// topped up. As seeded activities are dated relative to the import, an
+/// activity counts as existing when one with the same name has the same
+/// organizers, whatever its date.
+fn import_seed(conn: &mut PgConnection, seed: Seed) -> Result<(), String> {
+    use schema::asociations;
+    use schema::members;
+    use schema::organizers;
+    use schema::users;
+
+    for user in seed.users {
+        let exists = users::table
+            .filter(users::email.eq(&user.email))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|x| x.to_string())?;
+        if exists == 0 {
+            create_user(
+                conn,
+                user.username,
+                user.email.clone(),
+                user.name,
+                user.surname,
+                &user.password,
+            )?;
+        }
+        if user.is_admin {
+            let created = find_user(conn, &user.email)?;
+            grant_admin(conn, created.id)?;
+        }
+    }
+
+    let created = diesel::insert_into(asociations::table)
+        .values(seed.asociations)
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map_err(|x| x.to_string())?;
+    println!("Created {created} asociations");
+
+    for manager in seed.managers {
+        let user = find_user(conn, &manager.user)?;
+        let manager_id = find_or_create_manager(
+            conn,
+            &user,
+            manager.name,
+            manager.contact_email,
+            manager.material_email,
+        )?;
+        for asociation in manager.asociations {
+            assign_manager(conn, manager_id, &asociation)?;
+        }
+    }
+
+    for member in seed.board {
+        let user = find_user(conn, &member.user)?;
+        let asociation = find_asociation(conn, &member.asociation)?;
+        diesel::insert_into(members::table)
+            .values(models::database::NaiveMember {
+                user_id: user.id,
+                asociation: asociation.id,
+                is_accepted: true,
+                accepted_date: Some(time::OffsetDateTime::now_utc().date()),
+                expiry_date: None,
+                label: None,
+                board_status: member.board_status,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .map_err(|x| x.to_string())?;
+    }
+
+    for activity in seed.activities {
+        if activity.organizers.len() != activity.people_in_charge.len() {
+            return Err(format!(
+                "Activity {} needs a person in charge per organizer",
+                activity.name
+            ));
+        }
+        let mut new_organizers = vec![];
+        for (asociation, person) in activity.organizers.iter().zip(&activity.people_in_charge) {
+            new_organizers.push((
+                find_asociation(conn, asociation)?.id,
+                find_user(conn, person)?.id,
+            ));
+        }
+
+        let mut organizer_ids = new_organizers.iter().map(|(a, _)| *a).collect::<Vec<_>>();
+        organizer_ids.sort();
+        let namesakes = schema::activities::table
+            .filter(schema::activities::name.eq(&activity.name))
+            .select(schema::activities::id)
+            .load::<i64>(conn)
+            .map_err(|x| x.to_string())?;
+        let mut exists = false;
+        for namesake in namesakes {
+            let mut namesake_organizers = organizers::table
+                .filter(organizers::activity.eq(namesake))
+                .select(organizers::asociation)
+                .load::<Uuid>(conn)
+                .map_err(|x| x.to_string())?;
+            namesake_organizers.sort();
+            exists |= namesake_organizers == organizer_ids;
+        }
+        if exists {
+            continue;
+        }
+
+        let day =
+            time::OffsetDateTime::now_utc().date() + time::Duration::days(activity.days_from_now);
+        let hour = time::Time::from_hms(activity.hour, 0, 0).map_err(|x| x.to_string())?;
+        let new_activity = diesel::insert_into(schema::activities::table)
+            .values(models::database::NaiveActivity {
+                name: activity.name,
+                description: activity.description,
+                room: activity.room,
+                initial_date: time::PrimitiveDateTime::new(day, hour),
+                is_multi_session: false,
+                is_creditable: false,
+                is_external: false,
+                is_accepted: true,
+                is_room_accepted: true,
+                is_media_accepted: true,
+                is_registration_needed: activity.is_registration_needed,
+                access: activity.access,
+                additional_info: None,
+            })
+            .returning(schema::activities::id)
+            .get_result::<i64>(conn)
+            .map_err(|x| x.to_string())?;
+        diesel::insert_into(organizers::table)
+            .values(
+                new_organizers
+                    .into_iter()
+                    .map(|(asociation, person)| models::database::NaiveOrganizer {
+                        asociation,
+                        activity: new_activity,
+                        person_in_charge: person,
+                    })
+                    .collect::<Vec<models::database::NaiveOrganizer>>(),
+            )
+            .execute(conn)
+            .map_err(|x| x.to_string())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use diesel::r2d2::PoolError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use poem::error::ResponseError;
+use poem::http::StatusCode;
+use poem::{IntoResponse, Response};
+use poem_openapi::{payload::Json, ApiResponse, Enum, Object};
+use std::fmt::{self, Display};
+
+pub type Result<T, E = ApiError> = std::result::Result<T, E>;
+
+#[derive(Enum, Clone, Copy, PartialEq, Debug)]
+#[oai(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Validation,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    Internal,
+}
+
+#[derive(Object, Debug)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Request field the error refers to, when there is one.
+    pub field: Option<String>,
+}
+
+#[derive(ApiResponse, Debug)]
+#[oai(bad_request_handler = "request_error")]
+pub enum ApiError {
+    /// The request is malformed or breaks a data constraint.
+    #[oai(status = 400)]
+    Validation(Json<ErrorBody>),
+    /// The session is missing, expired or revoked.
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorBody>),
+    /// The session lacks the permissions the endpoint requires.
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The request clashes with the current state of a resource.
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 413)]
+    PayloadTooLarge(Json<ErrorBody>),
+    #[oai(status = 415)]
+    UnsupportedMediaType(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Internal(Json<ErrorBody>),
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>, field: Option<&str>) -> ApiError {
+        let body = Json(ErrorBody {
+            code,
+            message: message.into(),
+            field: field.map(str::to_owned),
+        });
+        match code {
+            ErrorCode::Validation => ApiError::Validation(body),
+            ErrorCode::Unauthorized => ApiError::Unauthorized(body),
+            ErrorCode::Forbidden => ApiError::Forbidden(body),
+            ErrorCode::NotFound => ApiError::NotFound(body),
+            ErrorCode::Conflict => ApiError::Conflict(body),
+            ErrorCode::PayloadTooLarge => ApiError::PayloadTooLarge(body),
+            ErrorCode::UnsupportedMediaType => ApiError::UnsupportedMediaType(body),
+            ErrorCode::Internal => ApiError::Internal(body),
+        }
+    }
+
+    pub fn validation(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::Validation, message, None)
+    }
+
+    pub fn invalid_field(field: &str, message: impl Display) -> ApiError {
+        ApiError::new(ErrorCode::Validation, message.to_string(), Some(field))
+    }
+
+    pub fn bad_request(error: impl Display) -> ApiError {
+        ApiError::validation(error.to_string())
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::Unauthorized, message, None)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::Forbidden, message, None)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::NotFound, message, None)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::Conflict, message, None)
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::PayloadTooLarge, message, None)
+    }
+
+    pub fn unsupported_media_type(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::UnsupportedMediaType, message, None)
+    }
+
+    /// Logs the underlying error and hides its details from the client.
+    pub fn internal(error: impl Display) -> ApiError {
+        tracing::error!("{error}");
+        ApiError::new(ErrorCode::Internal, "Internal server error", None)
+    }
+
+    pub fn body(&self) -> &ErrorBody {
+        match self {
+            ApiError::Validation(body)
+            | ApiError::Unauthorized(body)
+            | ApiError::Forbidden(body)
+            | ApiError::NotFound(body)
+            | ApiError::Conflict(body)
+            | ApiError::PayloadTooLarge(body)
+            | ApiError::UnsupportedMediaType(body)
+            | ApiError::Internal(body) => &body.0,
+        }
+    }
+}
+
+impl From<DieselError> for ApiError {
+    fn from(db_error: DieselError) -> ApiError {
+        match db_error {
+            DieselError::NotFound => ApiError::not_found("Resource not found"),
+            DieselError::DatabaseError(kind, info) => {
+                let code = match kind {
+                    DatabaseErrorKind::UniqueViolation => ErrorCode::Conflict,
+                    DatabaseErrorKind::ForeignKeyViolation
+                    | DatabaseErrorKind::NotNullViolation
+                    | DatabaseErrorKind::CheckViolation => ErrorCode::Validation,
+                    _ => return ApiError::internal(info.message()),
+                };
+                ApiError::new(code, info.message(), info.column_name())
+            }
+            e => ApiError::internal(e),
+        }
+    }
+}
+
+impl From<PoolError> for ApiError {
+    fn from(pool_error: PoolError) -> ApiError {
+        ApiError::internal(pool_error)
+    }
+}
+
+/// Carries an [`ApiError`] through a `poem::Error`, so that extractors can
+/// reject a request without losing its message and field.
+#[derive(Debug)]
+pub struct Rejection(pub ApiError);
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.body().message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl ResponseError for Rejection {
+    fn status(&self) -> StatusCode {
+        match self.0.body().code {
+            ErrorCode::Validation => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let body = self.0.body();
+        ApiError::new(body.code, body.message.clone(), body.field.as_deref()).into_response()
+    }
+}
+
+/// Keeps errors raised by poem itself (e.g. while reading a multipart body)
+/// in the same shape as the rest of the API.
+impl From<poem::Error> for ApiError {
+    fn from(error: poem::Error) -> ApiError {
+        let error = match error.downcast::<Rejection>() {
+            Ok(rejection) => return rejection.0,
+            Err(error) => error,
+        };
+        let code = match error.status() {
+            StatusCode::BAD_REQUEST => ErrorCode::Validation,
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            _ => return ApiError::internal(error),
+        };
+        ApiError::new(code, error.to_string(), None)
+    }
+}
+
+fn request_error(error: poem::Error) -> ApiError {
+    ApiError::from(error)
+}
@@ -0,0 +1,131 @@
+use crate::settings::Settings;
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+#[cfg(test)]
+use std::sync::Mutex;
+
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub cc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: Mail) -> Result<(), String>;
+}
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &Settings) -> Result<SmtpMailer, String> {
+        let port = settings
+            .smtp_port
+            .parse::<u16>()
+            .map_err(|x| x.to_string())?;
+        let host = settings
+            .smtp_host
+            .as_deref()
+            .ok_or("SMTP_HOST is not set")?;
+        let transport = if settings.smtp_username.is_empty() {
+            SmtpTransport::builder_dangerous(host).port(port)
+        } else {
+            SmtpTransport::starttls_relay(host)
+                .map_err(|x| x.to_string())?
+                .port(port)
+                .credentials(Credentials::new(
+                    settings.smtp_username.clone(),
+                    settings.smtp_password.clone(),
+                ))
+        }
+        .build();
+
+        Ok(SmtpMailer {
+            from: settings.mail_from.parse().map_err(|x| format!("{x}"))?,
+            transport,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: Mail) -> Result<(), String> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .to(mail.to.parse().map_err(|x| format!("{x}"))?);
+        for cc in mail.cc {
+            message = message.cc(cc.parse().map_err(|x| format!("{x}"))?);
+        }
+        let message = message
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|x| x.to_string())?;
+        self.transport.send(&message).map_err(|x| x.to_string())?;
+        Ok(())
+    }
+}
+
+/// Keeps mails in memory instead of sending them, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Mail>>,
+}
+
+#[cfg(test)]
+impl MemoryMailer {
+    /// Mails captured so far, oldest first.
+    pub fn sent(&self) -> Vec<Mail> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Mailer for MemoryMailer {
+    fn send(&self, mail: Mail) -> Result<(), String> {
+        tracing::info!("Captured mail to {}: {}", mail.to, mail.subject);
+        self.sent.lock().unwrap().push(mail);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail(to: &str, subject: &str) -> Mail {
+        Mail {
+            to: to.to_string(),
+            cc: vec![],
+            subject: subject.to_string(),
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn memory_mailer_captures_mails_in_order() {
+        let mailer = MemoryMailer::default();
+        assert!(mailer.sent().is_empty());
+
+        mailer.send(mail("ana@example.com", "First")).unwrap();
+        mailer
+            .send(Mail {
+                cc: vec!["board@example.com".to_string()],
+                ..mail("luis@example.com", "Second")
+            })
+            .unwrap();
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].to, "ana@example.com");
+        assert_eq!(sent[0].subject, "First");
+        assert_eq!(sent[1].to, "luis@example.com");
+        assert_eq!(sent[1].cc, vec!["board@example.com".to_string()]);
+    }
+}
@@ -1,40 +1,73 @@
 pub mod api;
 pub mod auth;
+pub mod cli;
+pub mod error;
+pub mod mail;
+pub mod migrations;
 pub mod models;
+pub mod policy;
+pub mod scheduler;
 pub mod schema;
 pub mod settings;
+pub mod storage;
+pub mod uploads;
 
 use std::env;
+use std::time::Duration;
 
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHasher,
 };
+use clap::Parser;
 use diesel::{
     r2d2::{ConnectionManager, Pool},
-    PgConnection, RunQueryDsl,
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+};
+use poem::{
+    listener::{BoxListener, Listener, RustlsCertificate, RustlsConfig, TcpListener},
+    middleware::Cors,
+    EndpointExt, Result, Route, Server,
 };
-use poem::{listener::TcpListener, middleware::Cors, EndpointExt, Result, Route, Server};
 use poem_openapi::OpenApiService;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
-    let package_version = env::var("CARGO_PKG_VERSION").unwrap_or("dev".to_string());
+    let cli = cli::Cli::parse();
     let settings = settings::load_settings();
-    let server_data = settings::get_server_data(settings);
-
 
     tracing_subscriber::fmt::init();
 
-    create_admin_user(&server_data.settings, server_data.data_pool.clone()).ok();
-    let url = format!(
-        "http://{}:{}",
-        &server_data.settings.hostname, &server_data.settings.port
-    );
+    match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => serve(settings).await,
+        command => cli::run(&settings, command).map_err(std::io::Error::other),
+    }
+}
+
+async fn serve(settings: settings::Settings) -> Result<(), std::io::Error> {
+    let package_version = env::var("CARGO_PKG_VERSION").unwrap_or("dev".to_string());
+    if settings.auto_migrate {
+        let conn =
+            &mut migrations::connect(&settings.database_url).map_err(std::io::Error::other)?;
+        for version in migrations::run_pending(conn).map_err(std::io::Error::other)? {
+            tracing::info!("Applied migration {version}");
+        }
+    }
+    let server_data = settings::get_server_data(settings);
+
+    if let Err(e) = create_admin_user(&server_data.settings, server_data.data_pool.clone()) {
+        tracing::warn!("Could not create the admin user: {e}");
+    }
+    scheduler::spawn(server_data.clone());
     let api_service = OpenApiService::new(api::DanubitApi, "Danubit", &package_version)
-        .server(format!("{}/{}", &url, "api"));
+        .server(format!("{}/api", &server_data.settings.public_url));
     let auth_service = OpenApiService::new(auth::DanubitAuthApi, "Danubit Auth", &package_version)
-        .server(format!("{}/{}", &url, "auth"));
+        .server(format!("{}/auth", &server_data.settings.public_url));
+
+    let listener = listener(&server_data.settings)?;
+    let shutdown_timeout = Duration::from_secs(server_data.settings.shutdown_timeout);
 
     let docs = api_service.swagger_ui();
     let spec = api_service.spec();
@@ -46,21 +79,83 @@ async fn main() -> Result<(), std::io::Error> {
         .nest("/auth", auth_service)
         .nest("/docs", docs)
         .nest("/docs/auth", auth_docs)
-        .at("/docs/spec", poem::endpoint::make_sync(move |_| spec.clone()))
-        .at("/docs/auth/spec", poem::endpoint::make_sync(move |_| auth_spec.clone()))
+        .at(
+            "/docs/spec",
+            poem::endpoint::make_sync(move |_| spec.clone()),
+        )
+        .at(
+            "/docs/auth/spec",
+            poem::endpoint::make_sync(move |_| auth_spec.clone()),
+        )
         .with(Cors::new())
         .data(server_data);
 
-    Server::new(TcpListener::bind("0.0.0.0:2345"))
-        .run(route)
-        .await?;
-    Ok(())
+    Server::new(listener)
+        .run_with_graceful_shutdown(route, shutdown_signal(), Some(shutdown_timeout))
+        .await
 }
 
+/// Builds the listener from the bind settings, terminating TLS when both a
+/// certificate and a key are configured.
+fn listener(settings: &settings::Settings) -> Result<BoxListener, std::io::Error> {
+    let listener = TcpListener::bind(format!("{}:{}", settings.bind_address, settings.port));
+    match (&settings.tls_cert_path, &settings.tls_key_path) {
+        (Some(cert), Some(key)) => {
+            let certificate = RustlsCertificate::new()
+                .cert(std::fs::read(cert)?)
+                .key(std::fs::read(key)?);
+            Ok(listener
+                .rustls(RustlsConfig::new().fallback(certificate))
+                .boxed())
+        }
+        (None, None) => Ok(listener.boxed()),
+        _ => Err(std::io::Error::other(
+            "TLS_CERT_PATH and TLS_KEY_PATH must be set together",
+        )),
+    }
+}
+
+/// Resolves on SIGTERM or Ctrl-C, after which in-flight requests are drained.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutting down");
+}
+
+/// Seeds the first platform admin. Once any admin exists it does nothing,
+/// and the role only ever goes to the account with the configured admin email,
+/// so signing up with the admin username grants nothing.
 fn create_admin_user(
     settings: &settings::Settings,
     connection_pool: Pool<ConnectionManager<PgConnection>>,
 ) -> Result<(), String> {
+    let conn = &mut connection_pool.get().map_err(|x| x.to_string())?;
+    let admins = schema::user_roles::table
+        .filter(schema::user_roles::role.eq(models::database::UserRole::PlatformAdmin))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|x| x.to_string())?;
+    if admins > 0 {
+        return Ok(());
+    }
+
     let password = env::var("ADMIN_PASSWORD").unwrap_or("admin".to_string());
     let salt = SaltString::generate(&mut OsRng);
     let hashed_password = Argon2::default()
@@ -72,19 +167,37 @@ fn create_admin_user(
         username: settings.admin_username.clone(),
         name: "".to_string(),
         surname: "".to_string(),
-        email: "admin@danubit.com".to_string(),
+        email: settings.admin_email.clone(),
         activated: true,
         password_hash: Some(hashed_password),
         additional_info: None,
     };
 
-    let conn = &mut connection_pool.get().map_err(|x| x.to_string())?;
-    diesel::insert_into(schema::users::table)
+    let inserted = diesel::insert_into(schema::users::table)
         .values(user)
         .on_conflict(schema::users::email)
         .do_nothing()
-        .execute(conn)
+        .returning(schema::users::id)
+        .get_result::<uuid::Uuid>(conn)
+        .optional()
         .map_err(|x| x.to_string())?;
+    // Anyone can sign up with the admin email before the first start, so an
+    // existing account is only promoted once its owner has verified it.
+    let admin_id = match inserted {
+        Some(id) => id,
+        None => schema::users::table
+            .filter(schema::users::email.eq(&settings.admin_email))
+            .filter(schema::users::activated.eq(true))
+            .select(schema::users::id)
+            .first::<uuid::Uuid>(conn)
+            .optional()
+            .map_err(|x| x.to_string())?
+            .ok_or(format!(
+                "{} belongs to an account that is not activated",
+                settings.admin_email
+            ))?,
+    };
+    cli::grant_admin(conn, admin_id)?;
 
     Ok(())
 }
@@ -0,0 +1,47 @@
+use diesel::migration::{Migration, MigrationSource};
+use diesel::pg::{Pg, PgConnection};
+use diesel::Connection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+pub fn connect(database_url: &str) -> Result<PgConnection, String> {
+    PgConnection::establish(database_url).map_err(|x| x.to_string())
+}
+
+/// Applies every pending migration, returning the versions that ran.
+pub fn run_pending(conn: &mut PgConnection) -> Result<Vec<String>, String> {
+    Ok(conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|x| x.to_string())?
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect())
+}
+
+/// Reverts the most recently applied migration.
+pub fn revert_last(conn: &mut PgConnection) -> Result<String, String> {
+    conn.revert_last_migration(MIGRATIONS)
+        .map(|v| v.to_string())
+        .map_err(|x| x.to_string())
+}
+
+/// Lists every embedded migration along with whether it has been applied.
+pub fn status(conn: &mut PgConnection) -> Result<Vec<(String, bool)>, String> {
+    let applied = conn
+        .applied_migrations()
+        .map_err(|x| x.to_string())?
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>();
+    let embedded = MigrationSource::<Pg>::migrations(&MIGRATIONS).map_err(|x| x.to_string())?;
+
+    Ok(embedded
+        .iter()
+        .map(|m| {
+            let version = m.name().version().to_string();
+            let is_applied = applied.contains(&version);
+            (m.name().to_string(), is_applied)
+        })
+        .collect())
+}
@@ -3,7 +3,7 @@ use crate::models::database as db;
 use diesel::prelude::*;
 use poem_openapi::{types::multipart, Multipart, Object};
 use serde::{Deserialize, Serialize};
-use time::Date;
+use time::{Date, PrimitiveDateTime};
 use uuid::Uuid;
 
 // API models
@@ -16,7 +16,6 @@ pub struct DocumentDescription {
     pub activity: Option<i64>,
     pub name: String,
     pub description: String,
-    pub is_current: bool,
     pub is_important: bool,
     pub is_manager_accessible: bool,
     pub is_public_accessible: bool,
@@ -62,12 +61,111 @@ pub struct NewFullActivity {
     pub people_in_charge: Vec<Uuid>,
 }
 
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct MediaReview {
+    pub is_accepted: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct PendingMedia {
+    pub activity: db::Activity,
+    pub media: Vec<db::Media>,
+}
+
 #[derive(Multipart, Debug)]
 pub struct MediaUpload {
     pub file_data: multipart::JsonField<MediaDescription>,
     pub upload: multipart::Upload,
 }
 
+/// Editable fields of a material. `available` is derived by the database
+/// from `quantity` and the approved lendings.
+#[derive(AsChangeset, Serialize, Deserialize, Object, Debug)]
+#[diesel(table_name = crate::schema::materials)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct MaterialUpdate {
+    pub name: String,
+    pub description: String,
+    pub quantity: i16,
+    pub is_lendable: bool,
+    pub is_shared: bool,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct LendingRequest {
+    pub quantity: i16,
+    pub due_date: Date,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct SharedLendingRequest {
+    pub borrower_asociation: Uuid,
+    pub quantity: i16,
+    pub due_date: Date,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct LendingReview {
+    pub is_approved: bool,
+}
+
+/// What other members get to see of a user, leaving out the credentials and
+/// account state of [`db::User`].
+#[derive(Queryable, Selectable, Serialize, Deserialize, Object, Debug)]
+#[diesel(table_name = crate::schema::users)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub username: String,
+    pub name: String,
+    pub surname: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct FullLending {
+    pub lending: db::Lending,
+    pub material: db::Material,
+    pub user: UserProfile,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct ReservationRequest {
+    pub quantity: i16,
+    pub starts_at: PrimitiveDateTime,
+    pub ends_at: PrimitiveDateTime,
+    /// Activity the material is booked for, if any.
+    pub activity: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Object, Debug)]
+pub struct FullReservation {
+    pub reservation: db::Reservation,
+    pub material: db::Material,
+    pub user: UserProfile,
+}
+
+/// Penalties an asociation applies to borrowers with overdue lendings, read
+/// from the `lending_policy` key of `asociations.info`, e.g.
+/// `{"lending_policy": {"block_loans_after_days": 7}}`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct LendingPolicy {
+    pub block_loans_after_days: Option<i64>,
+    pub block_renewal_after_days: Option<i64>,
+}
+
+impl LendingPolicy {
+    pub fn of(asociation: &db::Asociation) -> LendingPolicy {
+        asociation
+            .info
+            .get("lending_policy")
+            .and_then(|policy| serde_json::from_value(policy.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Multipart, Debug)]
 pub struct DocumentUpload {
     pub file_data: multipart::JsonField<DocumentDescription>,
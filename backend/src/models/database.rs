@@ -40,6 +40,34 @@ pub enum MediaKind {
     Extra,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Enum, DbEnum, Debug)]
+#[serde(rename_all = "snake_case")]
+#[ExistingTypePath = "crate::schema::sql_types::UserRole"]
+pub enum UserRole {
+    PlatformAdmin,
+    FacultyStaff,
+    Manager,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Enum, DbEnum, Debug)]
+#[serde(rename_all = "snake_case")]
+#[ExistingTypePath = "crate::schema::sql_types::LendingStatus"]
+pub enum LendingStatus {
+    Requested,
+    Approved,
+    Rejected,
+    Returned,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Enum, DbEnum, Debug)]
+#[serde(rename_all = "snake_case")]
+#[ExistingTypePath = "crate::schema::sql_types::MediaReviewStatus"]
+pub enum MediaReviewStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
 #[derive(
     Queryable, Selectable, Serialize, Deserialize, Identifiable, AsChangeset, Object, Debug,
 )]
@@ -54,6 +82,7 @@ pub struct User {
     pub activated: bool,
     pub password_hash: Option<String>,
     pub additional_info: Json,
+    pub token_generation: i32,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Object, Debug)]
@@ -186,9 +215,12 @@ pub struct Document {
     pub is_important: bool,
     pub is_manager_accessible: bool,
     pub is_public_accessible: bool,
+    pub version: i32,
+    /// SHA-256 of the file, hex encoded, used as its ETag.
+    pub digest: Option<String>,
 }
 
-#[derive(AsChangeset, Serialize, Deserialize, Object, Debug)]
+#[derive(Insertable, AsChangeset, Serialize, Deserialize, Object, Debug)]
 #[diesel(table_name = crate::schema::documents)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct NaiveDocument {
@@ -202,6 +234,8 @@ pub struct NaiveDocument {
     pub is_important: bool,
     pub is_manager_accessible: bool,
     pub is_public_accessible: bool,
+    pub version: i32,
+    pub digest: Option<String>,
 }
 
 #[derive(
@@ -215,7 +249,12 @@ pub struct Material {
     pub name: String,
     pub description: String,
     pub quantity: i16,
+    /// Units not out on an approved lending, kept up to date by the database.
+    #[oai(read_only)]
+    pub available: i16,
     pub is_lendable: bool,
+    /// Whether other asociations may borrow the material.
+    pub is_shared: bool,
 }
 
 #[derive(Insertable, Serialize, AsChangeset, Deserialize, Object, Debug)]
@@ -227,9 +266,10 @@ pub struct NaiveMaterial {
     pub description: String,
     pub quantity: i16,
     pub is_lendable: bool,
+    pub is_shared: bool,
 }
 
-#[derive(Queryable, Selectable)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Object, Debug)]
 #[diesel(table_name = crate::schema::lendings)]
 #[diesel(belongs_to(Material, foreign_key=material))]
 #[diesel(belongs_to(User, foreign_key=user_id))]
@@ -240,9 +280,53 @@ pub struct Lending {
     pub user_id: Uuid,
     pub quantity: i16,
     pub due_date: Date,
+    pub status: LendingStatus,
+    pub requested_at: PrimitiveDateTime,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<PrimitiveDateTime>,
+    pub returned_at: Option<PrimitiveDateTime>,
+    pub last_reminder_date: Option<Date>,
+    /// Asociation borrowing the material, for loans between asociations.
+    pub borrower_asociation: Option<Uuid>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::lendings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveLending {
+    pub material: i64,
+    pub user_id: Uuid,
+    pub quantity: i16,
+    pub due_date: Date,
+    pub borrower_asociation: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Object, Debug)]
+#[diesel(table_name = crate::schema::reservations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Reservation {
+    pub id: i64,
+    pub material: i64,
+    pub user_id: Uuid,
+    pub activity: Option<i64>,
+    pub quantity: i16,
+    pub starts_at: PrimitiveDateTime,
+    pub ends_at: PrimitiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::reservations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveReservation {
+    pub material: i64,
+    pub user_id: Uuid,
+    pub activity: Option<i64>,
+    pub quantity: i16,
+    pub starts_at: PrimitiveDateTime,
+    pub ends_at: PrimitiveDateTime,
 }
 
-#[derive(Queryable, Selectable)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Object, Debug)]
 #[diesel(table_name = crate::schema::media)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Media {
@@ -251,6 +335,18 @@ pub struct Media {
     pub activity: Option<i64>,
     pub kind: MediaKind,
     pub path: String,
+    pub review_status: MediaReviewStatus,
+    pub review_comment: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::media)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveMedia {
+    pub name: String,
+    pub activity: Option<i64>,
+    pub kind: MediaKind,
+    pub path: String,
 }
 
 #[derive(Serialize, Deserialize, Object, Debug)]
@@ -320,3 +416,47 @@ pub struct Registration {
     pub user_id: Option<Uuid>,
     pub registration_data: Json,
 }
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::password_resets)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaivePasswordReset {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: PrimitiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::email_verifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveEmailVerification {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: PrimitiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::refresh_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveRefreshToken {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: PrimitiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::invitations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveInvitation {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: PrimitiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::user_roles)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NaiveUserRole {
+    pub user_id: Uuid,
+    pub role: UserRole,
+}
@@ -0,0 +1,337 @@
+use crate::api::JWTBearerAuth;
+use crate::auth::AuthScheme;
+use crate::error::{ApiError, Rejection, Result};
+use crate::models::database::UserRole;
+use crate::settings::ServerData;
+
+use poem::{Request, RequestBody};
+use poem_openapi::{registry::Registry, ApiExtractor, ApiExtractorType, ExtractParamOptions};
+use std::marker::PhantomData;
+use uuid::Uuid;
+
+/// Access requirement of an endpoint, scoped to the resource it acts on.
+///
+/// Policies only look at the claims of the session, so any resource lookup
+/// (e.g. the organizers of an activity) has to happen before building them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Policy {
+    Public,
+    Authenticated,
+    Admin,
+    MemberOf(Uuid),
+    BoardOf(Uuid),
+    ChairOf(Uuid),
+    ManagerOf(Uuid),
+    /// Board status in at least one of the asociations organizing an activity.
+    OrganizerOf(Vec<Uuid>),
+    /// Board status in every asociation organizing an activity, so that
+    /// co-organizers cannot act on each other's behalf.
+    OwnerOf(Vec<Uuid>),
+    /// Manager of at least one of the asociations organizing an activity.
+    ManagerOfAny(Vec<Uuid>),
+    AnyOf(Vec<Policy>),
+}
+
+impl Policy {
+    pub fn or(self, other: Policy) -> Policy {
+        match self {
+            Policy::AnyOf(mut policies) => {
+                policies.push(other);
+                Policy::AnyOf(policies)
+            }
+            policy => Policy::AnyOf(vec![policy, other]),
+        }
+    }
+
+    pub fn allows(&self, auth_scheme: Option<&AuthScheme>) -> bool {
+        let auth_scheme = match (self, auth_scheme) {
+            (Policy::Public, _) => return true,
+            (Policy::AnyOf(policies), _) => return policies.iter().any(|p| p.allows(auth_scheme)),
+            (_, None) => return false,
+            (_, Some(auth_scheme)) => auth_scheme,
+        };
+
+        let is_chair = |a: &Uuid| auth_scheme.chair_of.contains(a);
+        let is_board = |a: &Uuid| auth_scheme.board_of.contains(a) || is_chair(a);
+        match self {
+            Policy::Authenticated => true,
+            Policy::Admin => auth_scheme.roles.contains(&UserRole::PlatformAdmin),
+            Policy::MemberOf(a) => auth_scheme.member_of.contains(a) || is_board(a),
+            Policy::BoardOf(a) => is_board(a),
+            Policy::ChairOf(a) => is_chair(a),
+            Policy::ManagerOf(a) => auth_scheme.manager_of.contains(a),
+            Policy::OrganizerOf(organizers) => organizers.iter().any(is_board),
+            Policy::OwnerOf(organizers) => {
+                !organizers.is_empty() && organizers.iter().all(is_board)
+            }
+            Policy::ManagerOfAny(organizers) => organizers
+                .iter()
+                .any(|a| auth_scheme.manager_of.contains(a)),
+            Policy::Public | Policy::AnyOf(_) => unreachable!(),
+        }
+    }
+
+    pub fn authorize(&self, auth_scheme: &AuthScheme) -> Result<()> {
+        self.check(Some(auth_scheme))
+    }
+
+    pub fn check(&self, auth_scheme: Option<&AuthScheme>) -> Result<()> {
+        if self.allows(auth_scheme) {
+            return Ok(());
+        }
+        match auth_scheme {
+            None => Err(ApiError::unauthorized("Authentication required")),
+            Some(_) => Err(ApiError::forbidden("Insufficient permissions")),
+        }
+    }
+}
+
+/// Requirement an endpoint declares through [`Authorized`], which resolves
+/// it to a [`Policy`] for the resource the request is scoped to.
+#[poem::async_trait]
+pub trait Requirement: Send + Sync {
+    async fn policy(req: &Request, data: &ServerData) -> Result<Policy>;
+}
+
+/// Bearer session of a request that satisfies the requirement `R`.
+///
+/// The session is checked before the endpoint runs, so a handler taking
+/// `Authorized<BoardOfAsociation>` only ever sees board members of the
+/// asociation in its path.
+pub struct Authorized<R>(pub AuthScheme, PhantomData<fn() -> R>);
+
+#[poem::async_trait]
+impl<'a, R: Requirement> ApiExtractor<'a> for Authorized<R> {
+    const TYPES: &'static [ApiExtractorType] = &[ApiExtractorType::SecurityScheme];
+
+    type ParamType = ();
+    type ParamRawType = ();
+
+    fn register(registry: &mut Registry) {
+        JWTBearerAuth::register(registry);
+    }
+
+    fn security_schemes() -> Vec<&'static str> {
+        JWTBearerAuth::security_schemes()
+    }
+
+    async fn from_request(
+        req: &'a Request,
+        body: &mut RequestBody,
+        param_opts: ExtractParamOptions<Self::ParamType>,
+    ) -> poem::Result<Self> {
+        let auth = <JWTBearerAuth as ApiExtractor>::from_request(req, body, param_opts).await?;
+        let data = req.data::<ServerData>().unwrap();
+        R::policy(req, data)
+            .await
+            .and_then(|policy| policy.authorize(&auth.0))
+            .map_err(|e| poem::Error::from(Rejection(e)))?;
+        Ok(Authorized(auth.0, PhantomData))
+    }
+}
+
+/// Either of two requirements.
+pub struct Or<A, B>(PhantomData<fn() -> (A, B)>);
+
+#[poem::async_trait]
+impl<A: Requirement, B: Requirement> Requirement for Or<A, B> {
+    async fn policy(req: &Request, data: &ServerData) -> Result<Policy> {
+        Ok(A::policy(req, data).await?.or(B::policy(req, data).await?))
+    }
+}
+
+pub struct Admin;
+
+#[poem::async_trait]
+impl Requirement for Admin {
+    async fn policy(_: &Request, _: &ServerData) -> Result<Policy> {
+        Ok(Policy::Admin)
+    }
+}
+
+/// Membership of the asociation in the `asociation_id` path parameter.
+pub struct MemberOfAsociation;
+
+#[poem::async_trait]
+impl Requirement for MemberOfAsociation {
+    async fn policy(req: &Request, _: &ServerData) -> Result<Policy> {
+        Ok(Policy::MemberOf(path_asociation(req)?))
+    }
+}
+
+/// Board status in the asociation in the `asociation_id` path parameter.
+pub struct BoardOfAsociation;
+
+#[poem::async_trait]
+impl Requirement for BoardOfAsociation {
+    async fn policy(req: &Request, _: &ServerData) -> Result<Policy> {
+        Ok(Policy::BoardOf(path_asociation(req)?))
+    }
+}
+
+/// Chair of the asociation in the `asociation_id` path parameter.
+pub struct ChairOfAsociation;
+
+#[poem::async_trait]
+impl Requirement for ChairOfAsociation {
+    async fn policy(req: &Request, _: &ServerData) -> Result<Policy> {
+        Ok(Policy::ChairOf(path_asociation(req)?))
+    }
+}
+
+fn path_asociation(req: &Request) -> Result<Uuid> {
+    Uuid::try_parse(req.raw_path_param("asociation_id").unwrap_or_default())
+        .map_err(|e| ApiError::invalid_field("asociation_id", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> AuthScheme {
+        AuthScheme {
+            sub: Uuid::new_v4(),
+            iat: 0,
+            exp: 0,
+            generation: 0,
+            username: "user".to_string(),
+            roles: vec![],
+            manager_of: vec![],
+            chair_of: vec![],
+            board_of: vec![],
+            member_of: vec![],
+        }
+    }
+
+    #[test]
+    fn public_allows_anonymous_requests() {
+        assert!(Policy::Public.allows(None));
+        assert!(!Policy::Authenticated.allows(None));
+        assert!(!Policy::BoardOf(Uuid::new_v4()).allows(None));
+    }
+
+    #[test]
+    fn board_of_includes_the_chair() {
+        let (board, chaired, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let auth = AuthScheme {
+            board_of: vec![board],
+            chair_of: vec![chaired],
+            ..session()
+        };
+        assert!(Policy::BoardOf(board).allows(Some(&auth)));
+        assert!(Policy::BoardOf(chaired).allows(Some(&auth)));
+        assert!(!Policy::BoardOf(other).allows(Some(&auth)));
+        assert!(Policy::MemberOf(board).allows(Some(&auth)));
+    }
+
+    #[test]
+    fn chair_of_excludes_the_rest_of_the_board() {
+        let (board, chaired) = (Uuid::new_v4(), Uuid::new_v4());
+        let auth = AuthScheme {
+            board_of: vec![board],
+            chair_of: vec![chaired],
+            ..session()
+        };
+        assert!(Policy::ChairOf(chaired).allows(Some(&auth)));
+        assert!(!Policy::ChairOf(board).allows(Some(&auth)));
+    }
+
+    #[test]
+    fn manager_of_is_independent_of_the_board() {
+        let (managed, board) = (Uuid::new_v4(), Uuid::new_v4());
+        let auth = AuthScheme {
+            manager_of: vec![managed],
+            board_of: vec![board],
+            ..session()
+        };
+        assert!(Policy::ManagerOf(managed).allows(Some(&auth)));
+        assert!(!Policy::ManagerOf(board).allows(Some(&auth)));
+        assert!(!Policy::BoardOf(managed).allows(Some(&auth)));
+        assert!(Policy::ManagerOfAny(vec![board, managed]).allows(Some(&auth)));
+        assert!(!Policy::ManagerOfAny(vec![]).allows(Some(&auth)));
+    }
+
+    #[test]
+    fn organizer_of_needs_one_organizing_board() {
+        let (board, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let auth = AuthScheme {
+            board_of: vec![board],
+            ..session()
+        };
+        assert!(Policy::OrganizerOf(vec![other, board]).allows(Some(&auth)));
+        assert!(!Policy::OrganizerOf(vec![other]).allows(Some(&auth)));
+        assert!(!Policy::OrganizerOf(vec![]).allows(Some(&auth)));
+    }
+
+    #[test]
+    fn owner_of_needs_every_organizing_board() {
+        let (board, chaired, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let auth = AuthScheme {
+            board_of: vec![board],
+            chair_of: vec![chaired],
+            ..session()
+        };
+        assert!(Policy::OwnerOf(vec![board, chaired]).allows(Some(&auth)));
+        assert!(!Policy::OwnerOf(vec![board, other]).allows(Some(&auth)));
+        assert!(!Policy::OwnerOf(vec![]).allows(Some(&auth)));
+    }
+
+    #[test]
+    fn any_of_allows_when_one_policy_does() {
+        let board = Uuid::new_v4();
+        let admin = AuthScheme {
+            roles: vec![UserRole::PlatformAdmin],
+            ..session()
+        };
+        let policy = Policy::Admin.or(Policy::BoardOf(board));
+        assert_eq!(
+            policy,
+            Policy::AnyOf(vec![Policy::Admin, Policy::BoardOf(board)])
+        );
+        assert!(policy.allows(Some(&admin)));
+        assert!(!policy.allows(Some(&session())));
+        assert!(!policy.allows(None));
+        assert!(!Policy::AnyOf(vec![]).allows(Some(&admin)));
+
+        let policy = policy.or(Policy::Public);
+        assert_eq!(
+            policy,
+            Policy::AnyOf(vec![Policy::Admin, Policy::BoardOf(board), Policy::Public])
+        );
+        assert!(policy.allows(None));
+    }
+
+    #[test]
+    fn check_tells_missing_sessions_from_missing_permissions() {
+        let policy = Policy::Admin;
+        assert!(matches!(policy.check(None), Err(ApiError::Unauthorized(_))));
+        assert!(matches!(
+            policy.check(Some(&session())),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejections_keep_their_error_body() {
+        let req = Request::builder().finish();
+        let error = poem::Error::from(Rejection(path_asociation(&req).unwrap_err()));
+        assert_eq!(error.status(), poem::http::StatusCode::BAD_REQUEST);
+        let body = error
+            .into_response()
+            .into_body()
+            .into_string()
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["code"], "validation");
+        assert_eq!(body["field"], "asociation_id");
+
+        let error = poem::Error::from(Rejection(Policy::Admin.check(None).unwrap_err()));
+        let error = ApiError::from(error);
+        assert!(matches!(error, ApiError::Unauthorized(_)));
+        assert_eq!(
+            error.body().message,
+            Policy::Admin.check(None).unwrap_err().body().message
+        );
+    }
+}
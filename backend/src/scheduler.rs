@@ -0,0 +1,120 @@
+use crate::error::Result;
+use crate::mail::Mail;
+use crate::models;
+use crate::schema;
+use crate::settings::ServerData;
+
+use diesel::prelude::*;
+use diesel::update;
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Starts the periodic jobs of the server on the async runtime.
+pub fn spawn(server_data: ServerData) {
+    if server_data.settings.reminder_interval == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(server_data.settings.reminder_interval));
+        loop {
+            interval.tick().await;
+            match server_data.run(send_overdue_reminders).await {
+                Ok(0) => {}
+                Ok(sent) => tracing::info!("Sent {sent} overdue lending reminders"),
+                Err(e) => tracing::error!("Overdue reminders failed: {}", e.body().message),
+            }
+        }
+    });
+}
+
+/// Mails every borrower with an overdue lending, plus one summary per
+/// asociation to its board. Each lending is reminded at most once a day, so
+/// restarts do not send duplicates.
+pub fn send_overdue_reminders(conn: &mut PgConnection, data: &ServerData) -> Result<usize> {
+    use schema::asociations;
+    use schema::lendings;
+    use schema::materials;
+    use schema::users;
+
+    let today = time::OffsetDateTime::now_utc().date();
+    let overdue: Vec<(
+        models::database::Lending,
+        (models::database::Material, models::database::Asociation),
+        models::database::User,
+    )> = lendings::table
+        .inner_join(materials::table.inner_join(asociations::table))
+        .inner_join(users::table)
+        .filter(lendings::status.eq(models::database::LendingStatus::Approved))
+        .filter(lendings::due_date.lt(today))
+        .filter(
+            lendings::last_reminder_date
+                .is_null()
+                .or(lendings::last_reminder_date.lt(today)),
+        )
+        .order(lendings::due_date)
+        .select((
+            models::database::Lending::as_select(),
+            (
+                models::database::Material::as_select(),
+                models::database::Asociation::as_select(),
+            ),
+            models::database::User::as_select(),
+        ))
+        .load(conn)?;
+
+    let mut reminded = vec![];
+    let mut summaries: HashMap<Uuid, (String, Vec<String>)> = HashMap::new();
+    for (lending, (material, asociation), user) in overdue {
+        let line = format!(
+            "{} x {} lent to {} {} ({}), due {}",
+            lending.quantity, material.name, user.name, user.surname, user.email, lending.due_date
+        );
+        let sent = data.mailer.send(Mail {
+            to: user.email.clone(),
+            cc: vec![],
+            subject: format!(
+                "Please return {} to {}",
+                material.name, asociation.short_name
+            ),
+            body: format!(
+                "Hi {},\n\n\
+                The {} x {} you borrowed from {} were due on {}.\n\
+                Please bring them back as soon as possible.",
+                user.name, lending.quantity, material.name, asociation.short_name, lending.due_date
+            ),
+        });
+        match sent {
+            Ok(()) => reminded.push(lending.id),
+            Err(e) => tracing::error!(
+                "Could not remind {} of lending {}: {e}",
+                user.email,
+                lending.id
+            ),
+        }
+        summaries
+            .entry(asociation.id)
+            .or_insert_with(|| (asociation.email.clone(), vec![]))
+            .1
+            .push(line);
+    }
+
+    for (email, lines) in summaries.into_values() {
+        let sent = data.mailer.send(Mail {
+            to: email.clone(),
+            cc: vec![],
+            subject: "Overdue lendings".to_string(),
+            body: format!("These lendings are overdue:\n\n{}", lines.join("\n")),
+        });
+        if let Err(e) = sent {
+            tracing::error!("Could not send the overdue summary to {email}: {e}");
+        }
+    }
+
+    update(lendings::table.filter(lendings::id.eq_any(&reminded)))
+        .set(lendings::last_reminder_date.eq(today))
+        .execute(conn)?;
+
+    Ok(reminded.len())
+}
@@ -9,9 +9,21 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "board_status"))]
     pub struct BoardStatus;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "lending_status"))]
+    pub struct LendingStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "media_kind"))]
     pub struct MediaKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "media_review_status"))]
+    pub struct MediaReviewStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "user_role"))]
+    pub struct UserRole;
 }
 
 diesel::table! {
@@ -70,16 +82,51 @@ diesel::table! {
         is_important -> Bool,
         is_manager_accessible -> Bool,
         is_public_accessible -> Bool,
+        version -> Int4,
+        #[max_length = 64]
+        digest -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    email_verifications (id) {
+        id -> Int8,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::table! {
+    invitations (id) {
+        id -> Int8,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::LendingStatus;
+
     lendings (id) {
         id -> Int8,
         material -> Int8,
         user_id -> Uuid,
         quantity -> Int2,
         due_date -> Date,
+        status -> LendingStatus,
+        requested_at -> Timestamp,
+        reviewed_by -> Nullable<Uuid>,
+        reviewed_at -> Nullable<Timestamp>,
+        returned_at -> Nullable<Timestamp>,
+        last_reminder_date -> Nullable<Date>,
+        borrower_asociation -> Nullable<Uuid>,
     }
 }
 
@@ -107,12 +154,14 @@ diesel::table! {
         quantity -> Int2,
         available -> Int2,
         is_lendable -> Bool,
+        is_shared -> Bool,
     }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::MediaKind;
+    use super::sql_types::MediaReviewStatus;
 
     media (id) {
         id -> Int8,
@@ -122,6 +171,8 @@ diesel::table! {
         kind -> MediaKind,
         #[max_length = 128]
         path -> Varchar,
+        review_status -> MediaReviewStatus,
+        review_comment -> Nullable<Text>,
     }
 }
 
@@ -151,6 +202,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    password_resets (id) {
+        id -> Int8,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Int8,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     registration (id) {
         id -> Int8,
@@ -160,6 +233,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    reservations (id) {
+        id -> Int8,
+        material -> Int8,
+        user_id -> Uuid,
+        activity -> Nullable<Int8>,
+        quantity -> Int2,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::UserRole;
+
+    user_roles (id) {
+        id -> Int8,
+        user_id -> Uuid,
+        role -> UserRole,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Uuid,
@@ -174,6 +270,7 @@ diesel::table! {
         #[max_length = 128]
         password_hash -> Nullable<Varchar>,
         additional_info -> Jsonb,
+        token_generation -> Int4,
     }
 }
 
@@ -181,6 +278,8 @@ diesel::joinable!(asociations -> managers (manager));
 diesel::joinable!(asociations -> media (logo));
 diesel::joinable!(documents -> activities (activity));
 diesel::joinable!(documents -> asociations (asociation));
+diesel::joinable!(email_verifications -> users (user_id));
+diesel::joinable!(invitations -> users (user_id));
 diesel::joinable!(lendings -> materials (material));
 diesel::joinable!(lendings -> users (user_id));
 diesel::joinable!(managers -> users (user_id));
@@ -191,19 +290,31 @@ diesel::joinable!(members -> users (user_id));
 diesel::joinable!(organizers -> activities (activity));
 diesel::joinable!(organizers -> asociations (asociation));
 diesel::joinable!(organizers -> users (person_in_charge));
+diesel::joinable!(password_resets -> users (user_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
 diesel::joinable!(registration -> activities (activity));
 diesel::joinable!(registration -> users (user_id));
+diesel::joinable!(reservations -> activities (activity));
+diesel::joinable!(reservations -> materials (material));
+diesel::joinable!(reservations -> users (user_id));
+diesel::joinable!(user_roles -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     activities,
     asociations,
     documents,
+    email_verifications,
+    invitations,
     lendings,
     managers,
     materials,
     media,
     members,
     organizers,
+    password_resets,
+    refresh_tokens,
     registration,
+    reservations,
+    user_roles,
     users,
 );
@@ -0,0 +1,92 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+pub trait FileStorage: Send + Sync {
+    fn store(&self, key: &str, contents: &[u8]) -> io::Result<()>;
+    fn retrieve(&self, key: &str) -> io::Result<Vec<u8>>;
+    fn remove(&self, key: &str) -> io::Result<()>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> LocalStorage {
+        LocalStorage { root: root.into() }
+    }
+
+    fn resolve(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid storage key",
+            ));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl FileStorage for LocalStorage {
+    fn store(&self, key: &str, contents: &[u8]) -> io::Result<()> {
+        let path = self.resolve(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    }
+
+    fn retrieve(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(key)?)
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.resolve(key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+pub fn document_key(asociation: &Uuid, file_name: Option<&str>) -> String {
+    let extension = file_name
+        .and_then(|f| Path::new(f).extension())
+        .and_then(|e| e.to_str())
+        .filter(|e| e.len() <= 8 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|e| format!(".{}", e.to_ascii_lowercase()))
+        .unwrap_or_default();
+    format!("documents/{}/{}{}", asociation, Uuid::new_v4(), extension)
+}
+
+pub fn content_type(key: &str) -> &'static str {
+    let extension = Path::new(key)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "odp" => "application/vnd.oasis.opendocument.presentation",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,154 @@
+use crate::models::database::{MediaKind, MediaReviewStatus};
+
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, ImageResult};
+use poem_openapi::types::multipart::Upload;
+use std::io::{self, Cursor};
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+const MEGABYTE: usize = 1024 * 1024;
+const THUMBNAIL_SIZE: u32 = 320;
+const SCREEN_SIZE: u32 = 1920;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Variant {
+    Original,
+    Thumbnail,
+    Screen,
+}
+
+impl Variant {
+    pub fn parse(variant: &str) -> Option<Variant> {
+        match variant {
+            "original" => Some(Variant::Original),
+            "thumbnail" => Some(Variant::Thumbnail),
+            "screen" => Some(Variant::Screen),
+            _ => None,
+        }
+    }
+
+    fn file_stem(&self) -> &'static str {
+        match self {
+            Variant::Original => "original",
+            Variant::Thumbnail => "thumbnail",
+            Variant::Screen => "screen",
+        }
+    }
+}
+
+pub fn max_upload_size(kind: &MediaKind) -> usize {
+    match kind {
+        MediaKind::Logo => 2 * MEGABYTE,
+        MediaKind::Digital | MediaKind::Screen => 10 * MEGABYTE,
+        MediaKind::Extra => 20 * MEGABYTE,
+        MediaKind::Print | MediaKind::Banner => 50 * MEGABYTE,
+    }
+}
+
+/// Reads `upload` into memory, giving up with `None` as soon as it goes over
+/// `limit` bytes instead of buffering the whole file first.
+pub async fn read_limited(upload: Upload, limit: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut contents = vec![];
+    upload
+        .into_async_read()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut contents)
+        .await?;
+    if contents.len() > limit {
+        return Ok(None);
+    }
+    Ok(Some(contents))
+}
+
+pub fn allowed_types(kind: &MediaKind) -> &'static [&'static str] {
+    match kind {
+        MediaKind::Logo => &["png", "jpg", "webp"],
+        MediaKind::Digital | MediaKind::Screen => &["png", "jpg", "webp", "gif"],
+        MediaKind::Print | MediaKind::Banner => &["png", "jpg", "pdf"],
+        MediaKind::Extra => &["png", "jpg", "webp", "gif", "pdf"],
+    }
+}
+
+pub fn requires_review(kind: &MediaKind) -> bool {
+    !matches!(kind, MediaKind::Extra)
+}
+
+/// Kinds an activity needs an accepted file of before its media counts as
+/// accepted, since the faculty only shows stamped posters on its screens.
+pub const REQUIRED_KINDS: [MediaKind; 3] =
+    [MediaKind::Digital, MediaKind::Print, MediaKind::Screen];
+
+/// Whether the media of an activity, given as the kind and review status of
+/// each file, is accepted: every required kind has an accepted file and no
+/// file that needs review is pending or rejected.
+pub fn media_accepted(reviews: &[(MediaKind, MediaReviewStatus)]) -> bool {
+    let has_accepted = |kind: &MediaKind| {
+        reviews
+            .iter()
+            .any(|(k, r)| k == kind && *r == MediaReviewStatus::Accepted)
+    };
+    REQUIRED_KINDS.iter().all(has_accepted)
+        && reviews
+            .iter()
+            .filter(|(k, _)| requires_review(k))
+            .all(|(_, r)| *r == MediaReviewStatus::Accepted)
+}
+
+pub fn sniff_type(contents: &[u8]) -> Option<&'static str> {
+    match contents {
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("webp"),
+        [b'%', b'P', b'D', b'F', ..] => Some("pdf"),
+        _ => None,
+    }
+}
+
+pub fn media_key(id: &Uuid, variant: Variant, extension: &str) -> String {
+    format!("media/{}/{}.{}", id, variant.file_stem(), extension)
+}
+
+pub fn variant_keys(original: &str) -> Vec<(Variant, String)> {
+    let directory = original.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+    [Variant::Thumbnail, Variant::Screen]
+        .into_iter()
+        .flat_map(|v| {
+            ["jpg", "png"]
+                .into_iter()
+                .map(move |e| (v, format!("{}/{}.{}", directory, v.file_stem(), e)))
+        })
+        .collect()
+}
+
+pub fn render_variants(contents: &[u8]) -> ImageResult<Vec<(Variant, &'static str, Vec<u8>)>> {
+    let Ok(image) = image::load_from_memory(contents) else {
+        return Ok(vec![]);
+    };
+
+    let mut variants = vec![];
+    for (variant, size) in [
+        (Variant::Thumbnail, THUMBNAIL_SIZE),
+        (Variant::Screen, SCREEN_SIZE),
+    ] {
+        let resized = if image.width() > size || image.height() > size {
+            image.thumbnail(size, size)
+        } else {
+            image.clone()
+        };
+        let (extension, encoded) = encode(&resized)?;
+        variants.push((variant, extension, encoded));
+    }
+    Ok(variants)
+}
+
+fn encode(image: &DynamicImage) -> ImageResult<(&'static str, Vec<u8>)> {
+    let mut buffer = vec![];
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        return Ok(("png", buffer));
+    }
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, 85))?;
+    Ok(("jpg", buffer))
+}